@@ -0,0 +1,258 @@
+use crate::eval::{MoveDelta, SquareIdx};
+use crabchess::prelude::*;
+
+// Bitboards follow `SquareIdx::to_index`: bit 0 is a8, bit 63 is h1
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+pub const LIGHT_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+
+pub const PAWN: usize = 0;
+pub const KNIGHT: usize = 1;
+pub const BISHOP: usize = 2;
+pub const ROOK: usize = 3;
+pub const QUEEN: usize = 4;
+pub const KING: usize = 5;
+
+pub const WHITE: usize = 0;
+pub const BLACK: usize = 1;
+
+pub fn piece_index(piece_type: Type) -> usize {
+    match piece_type {
+        Type::Pawn => PAWN,
+        Type::Knight => KNIGHT,
+        Type::Bishop => BISHOP,
+        Type::Rook => ROOK,
+        Type::Queen => QUEEN,
+        Type::King => KING,
+    }
+}
+
+pub fn color_index(color: Color) -> usize {
+    if color == Color::White { WHITE } else { BLACK }
+}
+
+pub fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+pub fn adjacent_files(file: usize) -> u64 {
+    let mut mask = 0;
+    if file > 0 {
+        mask |= file_mask(file - 1);
+    }
+    if file < 7 {
+        mask |= file_mask(file + 1);
+    }
+    mask
+}
+
+pub fn rank_mask(rank: usize) -> u64 {
+    0xFF << ((7 - rank) * 8)
+}
+
+// Every square on the ranks strictly in front of `rank` for `color`
+pub fn forward_ranks(color: usize, rank: usize) -> u64 {
+    let mut mask = 0;
+    for r in 0..8 {
+        if (color == WHITE && r > rank) || (color == BLACK && r < rank) {
+            mask |= rank_mask(r);
+        }
+    }
+    mask
+}
+
+// Rank of the square counted from `color`'s own back rank
+pub fn relative_rank(color: usize, rank: usize) -> usize {
+    if color == WHITE { rank } else { 7 - rank }
+}
+
+pub fn square_file(sq: usize) -> usize {
+    sq % 8
+}
+
+pub fn square_rank(sq: usize) -> usize {
+    7 - sq / 8
+}
+
+pub fn square_at(file: usize, rank: usize) -> usize {
+    (7 - rank) * 8 + file
+}
+
+pub fn distance(a: usize, b: usize) -> i32 {
+    let files = square_file(a).abs_diff(square_file(b));
+    let ranks = square_rank(a).abs_diff(square_rank(b));
+    files.max(ranks) as i32
+}
+
+pub fn squares(mut bitboard: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let sq = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(sq)
+    })
+}
+
+// Squares reached from `sq` by each (file, rank) step, sliding until a blocker when `slide`
+fn step_attacks(sq: usize, steps: &[(i32, i32)], slide: bool, occupied: u64) -> u64 {
+    let mut attacks = 0;
+    for &(df, dr) in steps {
+        let mut file = square_file(sq) as i32 + df;
+        let mut rank = square_rank(sq) as i32 + dr;
+        while (0..8).contains(&file) && (0..8).contains(&rank) {
+            let target = square_at(file as usize, rank as usize);
+            attacks |= 1 << target;
+            if !slide || occupied & (1 << target) != 0 {
+                break;
+            }
+            file += df;
+            rank += dr;
+        }
+    }
+    attacks
+}
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+const BISHOP_STEPS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const ROOK_STEPS: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+pub fn knight_attacks(sq: usize) -> u64 {
+    step_attacks(sq, &KNIGHT_STEPS, false, 0)
+}
+
+pub fn king_attacks(sq: usize) -> u64 {
+    step_attacks(sq, &KING_STEPS, false, 0)
+}
+
+pub fn bishop_attacks(sq: usize, occupied: u64) -> u64 {
+    step_attacks(sq, &BISHOP_STEPS, true, occupied)
+}
+
+pub fn rook_attacks(sq: usize, occupied: u64) -> u64 {
+    step_attacks(sq, &ROOK_STEPS, true, occupied)
+}
+
+pub fn queen_attacks(sq: usize, occupied: u64) -> u64 {
+    bishop_attacks(sq, occupied) | rook_attacks(sq, occupied)
+}
+
+// Squares attacked by every pawn of `pawns` belonging to `color`
+pub fn pawn_attacks(color: usize, pawns: u64) -> u64 {
+    if color == WHITE {
+        ((pawns & !FILE_A) >> 9) | ((pawns & !FILE_H) >> 7)
+    } else {
+        ((pawns & !FILE_A) << 7) | ((pawns & !FILE_H) << 9)
+    }
+}
+
+// Empty squares reached by single and double pushes of `pawns` belonging to `color`
+pub fn pawn_pushes(color: usize, pawns: u64, occupied: u64) -> u64 {
+    let (single, double) = if color == WHITE {
+        let single = (pawns >> 8) & !occupied;
+        (single, ((single & rank_mask(2)) >> 8) & !occupied)
+    } else {
+        let single = (pawns << 8) & !occupied;
+        (single, ((single & rank_mask(5)) << 8) & !occupied)
+    };
+    single | double
+}
+
+/// Piece placement of a position as bitboards, with the castling rights left
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bitboards {
+    pub pieces: [[u64; 6]; 2],
+    pub colors: [u64; 2],
+    pub occupied: u64,
+    // one bit per right, in KQkq order
+    pub castling: u8,
+}
+
+// Corner each castling right needs its rook on, in KQkq order
+const CASTLING_ROOKS: [usize; 4] = [63, 56, 7, 0];
+
+impl Bitboards {
+    pub fn new(pos: &ChessPosition) -> Self {
+        let mut board = Bitboards {
+            pieces: [[0; 6]; 2],
+            colors: [0; 2],
+            occupied: 0,
+            castling: 0,
+        };
+
+        for &file in File::all().iter() {
+            for &rank in Rank::all().iter() {
+                let sq = Square(file, rank);
+                if let Some(piece) = pos.get(sq) {
+                    let bit = 1 << sq.to_index();
+                    let color = color_index(piece.color);
+                    board.pieces[color][piece_index(piece.piece_type)] |= bit;
+                    board.colors[color] |= bit;
+                    board.occupied |= bit;
+                }
+            }
+        }
+
+        // castling rights are only available from the FEN
+        let fen = pos.fen();
+        let rights = fen.split_whitespace().nth(2).unwrap_or("-");
+        for (i, right) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if rights.contains(right) {
+                board.castling |= 1 << i;
+            }
+        }
+
+        board
+    }
+
+    // Bitboards of the position reached by a move
+    pub fn update(&self, delta: &MoveDelta) -> Self {
+        let mut board = *self;
+        for &(color, piece, sq) in delta.removed().iter().chain(delta.added()) {
+            let bit = 1 << sq;
+            board.pieces[color][piece] ^= bit;
+            board.colors[color] ^= bit;
+            board.occupied ^= bit;
+        }
+        // a king leaving its square loses both rights, a rook leaving or taken on its
+        // corner the one on its side
+        for &(color, piece, sq) in delta.removed() {
+            for side in 0..2 {
+                let right = 2 * color + side;
+                if piece == KING || (piece == ROOK && sq == CASTLING_ROOKS[right]) {
+                    board.castling &= !(1 << right);
+                }
+            }
+        }
+        board
+    }
+
+    pub fn can_castle(&self, color: usize, kingside: bool) -> bool {
+        let right = 2 * color + if kingside { 0 } else { 1 };
+        self.castling & (1 << right) != 0
+    }
+
+    pub fn king(&self, color: usize) -> usize {
+        self.pieces[color][KING].trailing_zeros() as usize
+    }
+}
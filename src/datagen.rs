@@ -0,0 +1,279 @@
+use crate::moves::{generate_legal_moves, is_irreversible};
+use crate::nnue::Network;
+use crate::params::EvalParams;
+use crate::pgn::{Game, PgnMove};
+use crate::san::san;
+use crate::search::*;
+use crate::zobrist::Zobrist;
+use crabchess::prelude::*;
+use rand::prelude::*;
+use rand::rng;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+};
+use std::thread;
+use std::time::Instant;
+
+// Games still going after this many plies are scored as draws
+const MAX_GAME_PLIES: usize = 400;
+
+/// Options of the `datagen` subcommand
+pub struct DatagenOptions {
+    pub output: String,
+    pub games: u64,
+    pub nodes: u64,
+    pub random_plies: usize,
+    pub threads: usize,
+    pub pgn: Option<String>,
+}
+
+impl DatagenOptions {
+    // datagen <output> [--games <n>] [--nodes <n>] [--random-plies <n>] [--threads <n>]
+    //         [--pgn <file>]
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = DatagenOptions {
+            output: String::new(),
+            games: 1000,
+            nodes: 5000,
+            random_plies: 8,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            pgn: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--games" => options.games = value()?.parse().map_err(|e| format!("{}", e))?,
+                "--nodes" => options.nodes = value()?.parse().map_err(|e| format!("{}", e))?,
+                "--random-plies" => {
+                    options.random_plies = value()?.parse().map_err(|e| format!("{}", e))?
+                }
+                "--threads" => options.threads = value()?.parse().map_err(|e| format!("{}", e))?,
+                "--pgn" => options.pgn = Some(value()?.clone()),
+                _ if options.output.is_empty() => options.output = arg.clone(),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if options.output.is_empty() {
+            return Err(String::from(
+                "Usage: datagen <output> [--games <n>] [--nodes <n>] [--random-plies <n>] [--threads <n>] [--pgn <file>]",
+            ));
+        }
+        options.threads = options.threads.max(1);
+        Ok(options)
+    }
+}
+
+// Self-play games at a fixed node count, each thread writing the quiet positions of its
+// games as "<fen> | <score> | <result>" lines, score in centipawns and result as 1.0, 0.5
+// or 0.0, both from White's point of view. The games themselves go to the PGN file when
+// one is given.
+pub fn run(
+    options: &DatagenOptions,
+    params: EvalParams,
+    network: Option<Network>,
+) -> Result<(), String> {
+    let file = File::create(&options.output).map_err(|e| format!("{}: {}", options.output, e))?;
+    let writer = Mutex::new(BufWriter::new(file));
+    let pgn_writer = match &options.pgn {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            Some(Mutex::new(BufWriter::new(file)))
+        }
+        None => None,
+    };
+    let started = AtomicU64::new(0);
+    let finished = AtomicU64::new(0);
+    let positions = AtomicU64::new(0);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for _ in 0..options.threads {
+            scope.spawn(|| {
+                let zob = Zobrist::new();
+                let mut rng = rng();
+                loop {
+                    let round = started.fetch_add(1, Ordering::Relaxed) + 1;
+                    if round > options.games {
+                        break;
+                    }
+                    let (lines, mut game) =
+                        play_game(options, &params, network.as_ref(), &zob, &mut rng);
+
+                    let mut writer = writer.lock().unwrap();
+                    for line in &lines {
+                        writeln!(writer, "{}", line).unwrap();
+                    }
+                    drop(writer);
+                    if let Some(pgn_writer) = &pgn_writer {
+                        game.set_tag("Event", "Copperfish self-play");
+                        game.set_tag("Round", &round.to_string());
+                        game.set_tag("White", "Copperfish");
+                        game.set_tag("Black", "Copperfish");
+                        write!(pgn_writer.lock().unwrap(), "{}", game.to_pgn()).unwrap();
+                    }
+
+                    let total = positions.fetch_add(lines.len() as u64, Ordering::Relaxed)
+                        + lines.len() as u64;
+                    let games = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                    if games.is_multiple_of(10) || games == options.games {
+                        println!(
+                            "{} games, {} positions, {:.0} positions/s",
+                            games,
+                            total,
+                            total as f64 / start.elapsed().as_secs_f64()
+                        );
+                    }
+                }
+            });
+        }
+    });
+
+    if let (Some(pgn_writer), Some(path)) = (pgn_writer, &options.pgn) {
+        pgn_writer
+            .into_inner()
+            .unwrap()
+            .flush()
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    writer
+        .into_inner()
+        .unwrap()
+        .flush()
+        .map_err(|e| format!("{}: {}", options.output, e))
+}
+
+// One game from a randomized opening, returning the lines of its recorded positions and
+// the game, every searched move commented with its score, depth and search time
+fn play_game(
+    options: &DatagenOptions,
+    params: &EvalParams,
+    network: Option<&Network>,
+    zob: &Zobrist,
+    rng: &mut ThreadRng,
+) -> (Vec<String>, Game) {
+    let mut game = Game::default();
+    let mut tt = TranspositionTable::new();
+    let mut position = ChessPosition::new();
+    let mut turn = Color::White;
+    let mut history = History::new(zob.hash_position(&position, turn), 0);
+
+    for _ in 0..options.random_plies {
+        let moves = generate_legal_moves(&position, turn, None, &[None; 2]);
+        let Some(&mv) = moves.choose(rng) else {
+            break;
+        };
+        game.moves.push(PgnMove::new(&san(&position, turn, &mv)));
+        let irreversible = is_irreversible(&position, &mv);
+        position.apply_move(mv).unwrap();
+        turn = turn.other();
+        history.push(zob.hash_position(&position, turn), irreversible);
+    }
+
+    let mut records = Vec::new();
+    let mut guess = 0;
+    let mut result = 0.5;
+    for _ in 0..MAX_GAME_PLIES {
+        match position.status() {
+            PositionStatus::InProgress => {}
+            PositionStatus::Checkmate(Color::White) => {
+                result = 1.0;
+                break;
+            }
+            PositionStatus::Checkmate(Color::Black) => {
+                result = 0.0;
+                break;
+            }
+            _ => break,
+        }
+        history.set_root();
+        if history.is_repetition() || history.halfmove_clock() >= 100 {
+            break;
+        }
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::new(
+            &mut tt,
+            zob,
+            params,
+            network,
+            &mut history,
+            Contempt::new(),
+            &stop_flag,
+        );
+        searcher.node_limit = Some(options.nodes);
+
+        let search_start = Instant::now();
+        let mut best = None;
+        for depth in 1..MAX_PLY {
+            let Some((mv, score)) = searcher.aspiration_search(&position, turn, guess, depth, 25)
+            else {
+                break;
+            };
+            // an unfinished iteration only counts when it is all we have
+            if stop_flag.load(Ordering::Relaxed) && best.is_some() {
+                break;
+            }
+            best = Some((mv, score, depth));
+            if stop_flag.load(Ordering::Relaxed) || mate_in(score).is_some() {
+                break;
+            }
+        }
+        let Some((mv, score, depth)) = best else {
+            break;
+        };
+        guess = score;
+
+        let mut record = PgnMove::new(&san(&position, turn, &mv));
+        record.comment = Some(format!("{}/{}", format_score(score, turn), depth));
+        record.set_clock("emt", search_start.elapsed());
+        game.moves.push(record);
+
+        // positions whose score hinges on a tactic are no use for training a static eval
+        let noisy = position.is_in_check(turn)
+            || mv.is_capture()
+            || position.get(mv.final_square()).is_some()
+            || matches!(mv, Move::PawnPromotion { .. });
+        if !noisy && mate_in(score).is_none() {
+            records.push((position.fen(), score));
+        }
+
+        let irreversible = is_irreversible(&position, &mv);
+        position.apply_move(mv).unwrap();
+        turn = turn.other();
+        history.push(zob.hash_position(&position, turn), irreversible);
+    }
+
+    game.result = String::from(match result {
+        1.0 => "1-0",
+        0.0 => "0-1",
+        _ => "1/2-1/2",
+    });
+    let lines = records
+        .into_iter()
+        .map(|(fen, score)| format_line(&fen, score, result))
+        .collect();
+    (lines, game)
+}
+
+// Score from the side to move's point of view, in pawns or as a mate distance such as
+// "+M3", the way PGN move comments usually give it
+fn format_score(score: i32, turn: Color) -> String {
+    let score = if turn == Color::White { score } else { -score };
+    match mate_in(score) {
+        Some(moves) if moves > 0 => format!("+M{}", moves),
+        Some(moves) => format!("-M{}", -moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+// A line of the output file, `score` and `result` being from White's point of view as the
+// search returns them and as `tune` reads them
+pub fn format_line(fen: &str, score: i32, result: f64) -> String {
+    format!("{} | {} | {:.1}", fen, score, result)
+}
@@ -0,0 +1,241 @@
+use crate::bitboard::*;
+use crate::kpk;
+use crate::params::EvalParams;
+use crate::pawns;
+
+// Scale factors of the endgame half of the evaluation, out of SCALE_NORMAL
+pub const SCALE_NORMAL: i32 = 64;
+const SCALE_DRAW: i32 = 0;
+
+// Evaluation of an endgame known to be won, above any material count and below mates
+pub const KNOWN_WIN: i32 = 10_000;
+
+// Material signatures, pawn to queen counts of one side next to its king
+const LONE_KING: [u32; 5] = [0, 0, 0, 0, 0];
+const KP: [u32; 5] = [1, 0, 0, 0, 0];
+const KBN: [u32; 5] = [0, 1, 1, 0, 0];
+const KR: [u32; 5] = [0, 0, 0, 1, 0];
+const KQ: [u32; 5] = [0, 0, 0, 0, 1];
+
+fn material(board: &Bitboards, color: usize) -> [u32; 5] {
+    std::array::from_fn(|piece| board.pieces[color][piece].count_ones())
+}
+
+// Evaluation of the endgames with a specialized evaluator, from White's point of view,
+// `turn` being the side to move
+pub fn evaluate(board: &Bitboards, turn: usize, params: &EvalParams) -> Option<i32> {
+    let material = [WHITE, BLACK].map(|color| material(board, color));
+    for strong in [WHITE, BLACK] {
+        let weak = strong ^ 1;
+        let eval = match (material[strong], material[weak]) {
+            (KBN, LONE_KING) => kbnk(board, strong, params),
+            (KP, LONE_KING) => kpk(board, strong, turn, params),
+            (KR, KP) => krkp(board, strong, turn, params),
+            (KQ, KR) => kqkr(board, strong, params),
+            (_, LONE_KING) if has_mating_material(board, strong) => kxk(board, strong, params),
+            _ => continue,
+        };
+        return Some(if strong == WHITE { eval } else { -eval });
+    }
+    None
+}
+
+// Exact evaluation of king and pawn against king from White's point of view, None for
+// any other material
+pub fn probe_kpk(board: &Bitboards, turn: usize, params: &EvalParams) -> Option<i32> {
+    let material = [WHITE, BLACK].map(|color| material(board, color));
+    let strong = match material {
+        [KP, LONE_KING] => WHITE,
+        [LONE_KING, KP] => BLACK,
+        _ => return None,
+    };
+    let eval = kpk(board, strong, turn, params);
+    Some(if strong == WHITE { eval } else { -eval })
+}
+
+// Fraction of the endgame value kept by `strong`, the side the endgame value favors
+pub fn scale_factor(board: &Bitboards, strong: usize, params: &EvalParams) -> i32 {
+    let weak = strong ^ 1;
+    let non_pawn = |color: usize| {
+        (KNIGHT..=QUEEN)
+            .map(|piece| {
+                board.pieces[color][piece].count_ones() as i32 * params.piece_values[piece].mg()
+            })
+            .sum::<i32>()
+    };
+    let (ours, theirs) = (non_pawn(strong), non_pawn(weak));
+    let bishop = params.piece_values[BISHOP].mg();
+    let rook = params.piece_values[ROOK].mg();
+
+    // without pawns, equal material or a minor piece up is not enough to win, as in
+    // KR vs KR, KNN vs K or KR vs KB
+    if board.pieces[strong][PAWN] == 0 {
+        let only_knights =
+            board.colors[strong] & !board.pieces[strong][KNIGHT] & !board.pieces[strong][KING] == 0;
+        if ours == theirs || only_knights && board.pieces[weak][PAWN] == 0 {
+            return SCALE_DRAW;
+        }
+        if ours - theirs <= bishop {
+            return if ours < rook {
+                SCALE_DRAW
+            } else if theirs <= bishop {
+                4
+            } else {
+                14
+            };
+        }
+    }
+
+    // opposite-colored bishops and pawns, drawish unless passed pawns are spread out
+    let bishops = [board.pieces[WHITE][BISHOP], board.pieces[BLACK][BISHOP]];
+    if ours == bishop
+        && theirs == bishop
+        && bishops[WHITE].count_ones() == 1
+        && bishops[BLACK].count_ones() == 1
+        && (bishops[WHITE] & LIGHT_SQUARES == 0) != (bishops[BLACK] & LIGHT_SQUARES == 0)
+    {
+        let entry = pawns::probe(
+            [board.pieces[WHITE][PAWN], board.pieces[BLACK][PAWN]],
+            params,
+        );
+        return 16 + 4 * entry.passed[strong].count_ones() as i32;
+    }
+
+    // rook pawns with a bishop that does not cover the queening corner the defending
+    // king already holds
+    let pawns = board.pieces[strong][PAWN];
+    if ours == bishop
+        && bishops[strong] != 0
+        && pawns != 0
+        && theirs == 0
+        && board.pieces[weak][PAWN] == 0
+    {
+        for file in [0, 7] {
+            let queening = square_at(file, relative_rank(strong, 7));
+            let bishop_light = bishops[strong] & LIGHT_SQUARES != 0;
+            let corner_light = LIGHT_SQUARES & (1 << queening) != 0;
+            if pawns & !file_mask(file) == 0
+                && bishop_light != corner_light
+                && distance(board.king(weak), queening) <= 1
+            {
+                return SCALE_DRAW;
+            }
+        }
+    }
+
+    SCALE_NORMAL
+}
+
+// Queen, rook, bishops of both colors or bishop and knight, enough to mate a lone king
+fn has_mating_material(board: &Bitboards, color: usize) -> bool {
+    let pieces = board.pieces[color];
+    pieces[QUEEN] != 0
+        || pieces[ROOK] != 0
+        || pieces[BISHOP] & LIGHT_SQUARES != 0 && pieces[BISHOP] & !LIGHT_SQUARES != 0
+        || pieces[BISHOP] != 0 && pieces[KNIGHT] != 0
+}
+
+// Bonus for a king pushed away from the centre, 0 to 120
+fn push_to_edge(sq: usize) -> i32 {
+    let file = square_file(sq) as i32;
+    let rank = square_rank(sq) as i32;
+    20 * ((3 - file).max(file - 4) + (3 - rank).max(rank - 4))
+}
+
+// Bonus for kings close to each other, 0 to 120
+fn push_close(a: usize, b: usize) -> i32 {
+    140 - 20 * distance(a, b)
+}
+
+fn material_eg(board: &Bitboards, color: usize, params: &EvalParams) -> i32 {
+    (PAWN..KING)
+        .map(|piece| {
+            board.pieces[color][piece].count_ones() as i32 * params.piece_values[piece].eg()
+        })
+        .sum()
+}
+
+// Squares seen from `color`'s side of the board, its pawns moving up the ranks
+fn relative_square(color: usize, sq: usize) -> usize {
+    if color == WHITE { sq } else { sq ^ 56 }
+}
+
+// Lone king against mating material: drive it to the edge and bring the other king closer
+fn kxk(board: &Bitboards, strong: usize, params: &EvalParams) -> i32 {
+    let weak_king = board.king(strong ^ 1);
+    KNOWN_WIN
+        + material_eg(board, strong, params)
+        + push_to_edge(weak_king)
+        + push_close(board.king(strong), weak_king)
+}
+
+// Bishop and knight only mate in a corner of the bishop's color, the defending king is
+// pushed away from the long diagonal of the other color
+fn kbnk(board: &Bitboards, strong: usize, params: &EvalParams) -> i32 {
+    let weak_king = board.king(strong ^ 1);
+    let mut file = square_file(weak_king) as i32;
+    if board.pieces[strong][BISHOP] & LIGHT_SQUARES != 0 {
+        file = 7 - file;
+    }
+    let to_corner = (7 - square_rank(weak_king) as i32 - file).abs();
+
+    KNOWN_WIN
+        + material_eg(board, strong, params)
+        + 60 * to_corner
+        + push_close(board.king(strong), weak_king)
+}
+
+// King and pawn against king, settled by the bitbase
+fn kpk(board: &Bitboards, strong: usize, turn: usize, params: &EvalParams) -> i32 {
+    let strong_king = relative_square(strong, board.king(strong));
+    let weak_king = relative_square(strong, board.king(strong ^ 1));
+    let pawn = relative_square(strong, board.pieces[strong][PAWN].trailing_zeros() as usize);
+    if !kpk::probe(strong_king, pawn, weak_king, turn == strong) {
+        return 0;
+    }
+    KNOWN_WIN + params.piece_values[PAWN].eg() + 10 * square_rank(pawn) as i32
+}
+
+// Rook against pawn, mostly a matter of which king gets to the pawn first
+fn krkp(board: &Bitboards, strong: usize, turn: usize, params: &EvalParams) -> i32 {
+    let weak = strong ^ 1;
+    // seen from the rook's side, the pawn running down the board
+    let strong_king = relative_square(strong, board.king(strong));
+    let weak_king = relative_square(strong, board.king(weak));
+    let rook = relative_square(strong, board.pieces[strong][ROOK].trailing_zeros() as usize);
+    let pawn = relative_square(strong, board.pieces[weak][PAWN].trailing_zeros() as usize);
+    let file = square_file(pawn);
+    let queening = square_at(file, 0);
+    let in_front = square_at(file, square_rank(pawn) - 1);
+    let rook_value = params.piece_values[ROOK].eg();
+
+    if square_file(strong_king) == file && square_rank(strong_king) < square_rank(pawn) {
+        // the winning king already stands in the pawn's way
+        rook_value - distance(strong_king, pawn)
+    } else if distance(weak_king, pawn) >= 3 + (turn == weak) as i32
+        && distance(weak_king, rook) >= 3
+    {
+        // the pawn is left alone, the rook picks it up
+        rook_value - distance(strong_king, pawn)
+    } else if square_rank(weak_king) <= 2
+        && distance(weak_king, pawn) == 1
+        && square_rank(strong_king) >= 3
+        && distance(strong_king, pawn) > 2 + (turn == strong) as i32
+    {
+        // an advanced pawn escorted by its king, with the other king too far away
+        80 - 8 * distance(strong_king, pawn)
+    } else {
+        200 - 8
+            * (distance(strong_king, in_front)
+                - distance(weak_king, in_front)
+                - distance(pawn, queening))
+    }
+}
+
+// Queen against rook: won, but the rook is taken only once the king is on the edge
+fn kqkr(board: &Bitboards, strong: usize, params: &EvalParams) -> i32 {
+    let weak_king = board.king(strong ^ 1);
+    params.piece_values[QUEEN].eg() - params.piece_values[ROOK].eg()
+        + push_to_edge(weak_king)
+        + push_close(board.king(strong), weak_king)
+}
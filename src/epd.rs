@@ -0,0 +1,333 @@
+use crate::eval::evaluate;
+use crate::moves::halfmove_clock;
+use crate::nnue::Network;
+use crate::params::EvalParams;
+use crate::san::{parse_san, san};
+use crate::search::*;
+use crate::uci::move_from_uci;
+use crate::zobrist::Zobrist;
+use crabchess::prelude::*;
+use std::fs;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Options of the `epd` subcommand
+pub struct EpdOptions {
+    pub input: String,
+    pub time: Option<Duration>,
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+}
+
+impl EpdOptions {
+    // epd <file> [--time <ms>] [--depth <n>] [--nodes <n>], one second per position
+    // when no limit is given
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = EpdOptions {
+            input: String::new(),
+            time: None,
+            depth: None,
+            nodes: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--time" => {
+                    let ms = value()?.parse().map_err(|e| format!("{}", e))?;
+                    options.time = Some(Duration::from_millis(ms));
+                }
+                "--depth" => options.depth = Some(value()?.parse().map_err(|e| format!("{}", e))?),
+                "--nodes" => options.nodes = Some(value()?.parse().map_err(|e| format!("{}", e))?),
+                _ if options.input.is_empty() => options.input = arg.clone(),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if options.input.is_empty() {
+            return Err(String::from(
+                "Usage: epd <file> [--time <ms>] [--depth <n>] [--nodes <n>]",
+            ));
+        }
+        if options.time.is_none() && options.depth.is_none() && options.nodes.is_none() {
+            options.time = Some(Duration::from_secs(1));
+        }
+        Ok(options)
+    }
+}
+
+/// A position of a test suite with its best and avoid moves, still in SAN
+pub struct Test {
+    pub id: String,
+    pub position: ChessPosition,
+    pub turn: Color,
+    pub best: Vec<String>,
+    pub avoid: Vec<String>,
+}
+
+/// Results of a test suite run
+#[derive(Default)]
+pub struct Tally {
+    pub solved: usize,
+    pub total: usize,
+    pub nodes: u64,
+    pub time: Duration,
+    pub failed: Vec<String>,
+}
+
+// "<4 FEN fields> <opcode> <operands>; ...", the move counters coming from the hmvc and
+// fmvn operations when present
+pub fn parse_line(line: &str) -> Result<Test, String> {
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+    if fields.len() < 4 {
+        return Err(String::from("missing FEN fields"));
+    }
+    let rest = line
+        .split_whitespace()
+        .skip(4)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut test = Test {
+        id: String::new(),
+        position: ChessPosition::new(),
+        turn: if fields[1] == "b" {
+            Color::Black
+        } else {
+            Color::White
+        },
+        best: Vec::new(),
+        avoid: Vec::new(),
+    };
+    let (mut halfmove, mut fullmove) = ("0", "1");
+    for operation in split_operations(&rest) {
+        let Some((opcode, operands)) = operation.trim().split_once(' ') else {
+            continue;
+        };
+        let operands = operands.trim();
+        let moves = || operands.split_whitespace().map(str::to_string).collect();
+        match opcode {
+            "bm" => test.best = moves(),
+            "am" => test.avoid = moves(),
+            "id" => test.id = operands.trim_matches('"').to_string(),
+            "hmvc" => halfmove = operands,
+            "fmvn" => fullmove = operands,
+            _ => {}
+        }
+    }
+
+    let fen = format!("{} {} {}", fields.join(" "), halfmove, fullmove);
+    test.position = ChessPosition::from_fen(&fen).map_err(|e| format!("{}: {}", fen, e))?;
+    if test.best.is_empty() && test.avoid.is_empty() {
+        return Err(String::from("no bm or am operation"));
+    }
+    Ok(test)
+}
+
+// Operations are separated by semicolons, which may also appear in quoted strings
+pub fn split_operations(text: &str) -> Vec<&str> {
+    let mut operations = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                operations.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    operations.push(&text[start..]);
+    operations
+}
+
+// Whether `found` is one of the moves of `list`, written in SAN or in UCI
+pub fn contains(test: &Test, list: &[String], found: Move) -> bool {
+    list.iter().any(|text| {
+        parse_san(&test.position, test.turn, text)
+            .ok()
+            .or_else(|| move_from_uci(&test.position, test.turn, text).ok())
+            == Some(found)
+    })
+}
+
+/// Outcome of the search of one position
+struct Found {
+    mv: Move,
+    score: i32,
+    depth: usize,
+    nodes: u64,
+    time: Duration,
+}
+
+// Iterative deepening from a fresh transposition table, keeping the result of the last
+// completed iteration
+fn search(
+    test: &Test,
+    options: &EpdOptions,
+    params: &EvalParams,
+    network: Option<&Network>,
+) -> Option<Found> {
+    let zob = Zobrist::new();
+    let mut tt = TranspositionTable::new();
+    let mut history = History::new(
+        zob.hash_position(&test.position, test.turn),
+        halfmove_clock(&test.position),
+    );
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let done = AtomicBool::new(false);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        if let Some(time) = options.time {
+            let (done, stop_flag) = (&done, &stop_flag);
+            scope.spawn(move || {
+                while start.elapsed() < time && !done.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                stop_flag.store(true, Ordering::Relaxed);
+            });
+        }
+
+        let mut searcher = Searcher::new(
+            &mut tt,
+            &zob,
+            params,
+            network,
+            &mut history,
+            Contempt::new(),
+            &stop_flag,
+        );
+        searcher.node_limit = options.nodes;
+
+        let mut guess = evaluate(&test.position, test.turn, params);
+        let mut found = None;
+        let max_depth = options.depth.unwrap_or(MAX_PLY - 1).min(MAX_PLY - 1);
+        for depth in 1..=max_depth {
+            let result = searcher.aspiration_search(&test.position, test.turn, guess, depth, 25);
+            let Some((mv, score)) = result else {
+                break;
+            };
+            // an unfinished iteration only counts when it is all we have
+            if stop_flag.load(Ordering::Relaxed) && found.is_some() {
+                break;
+            }
+            guess = score;
+            found = Some(Found {
+                mv,
+                score,
+                depth,
+                nodes: searcher.nodes,
+                time: start.elapsed(),
+            });
+            if stop_flag.load(Ordering::Relaxed) || mate_in(score).is_some() {
+                break;
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+        found.map(|found| Found {
+            nodes: searcher.nodes,
+            time: start.elapsed(),
+            ..found
+        })
+    })
+}
+
+// Score from the side to move's point of view, in pawns or as a mate distance
+fn format_score(score: i32, turn: Color) -> String {
+    let score = if turn == Color::White { score } else { -score };
+    match mate_in(score) {
+        Some(moves) => format!("#{}", moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+// Whether the move found plays one of the best moves and none of the avoid moves
+pub fn is_solved(test: &Test, found: Move) -> bool {
+    (test.best.is_empty() || contains(test, &test.best, found))
+        && !contains(test, &test.avoid, found)
+}
+
+// Searches every position of the file and prints whether its best move was found or its
+// avoid move avoided, then the solved count
+pub fn run(
+    options: &EpdOptions,
+    params: EvalParams,
+    network: Option<Network>,
+) -> Result<Tally, String> {
+    let text =
+        fs::read_to_string(&options.input).map_err(|e| format!("{}: {}", options.input, e))?;
+    let mut tally = Tally::default();
+
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let test = match parse_line(line) {
+            Ok(test) => test,
+            Err(e) => {
+                eprintln!("{}:{}: {}", options.input, number + 1, e);
+                continue;
+            }
+        };
+        tally.total += 1;
+        let id = if test.id.is_empty() {
+            format!("#{}", number + 1)
+        } else {
+            test.id.clone()
+        };
+
+        let Some(found) = search(&test, options, &params, network.as_ref()) else {
+            println!("{:<16} no legal move", id);
+            tally.failed.push(id);
+            continue;
+        };
+        let ok = is_solved(&test, found.mv);
+        if ok {
+            tally.solved += 1;
+        } else {
+            tally.failed.push(id.clone());
+        }
+        tally.nodes += found.nodes;
+        tally.time += found.time;
+
+        let expected = if test.best.is_empty() {
+            format!("am {}", test.avoid.join(" "))
+        } else {
+            format!("bm {}", test.best.join(" "))
+        };
+        println!(
+            "{:<16} {:<7} {:<16} found {:<8} score {:>7}  depth {:>2}  nodes {:>9}  time {:>6.2}s",
+            id,
+            if ok { "solved" } else { "failed" },
+            expected,
+            san(&test.position, test.turn, &found.mv),
+            format_score(found.score, test.turn),
+            found.depth,
+            found.nodes,
+            found.time.as_secs_f64()
+        );
+    }
+
+    println!();
+    println!(
+        "Solved {} of {} ({:.1}%), {} nodes in {:.2}s, {:.0} nps",
+        tally.solved,
+        tally.total,
+        100.0 * tally.solved as f64 / tally.total.max(1) as f64,
+        tally.nodes,
+        tally.time.as_secs_f64(),
+        tally.nodes as f64 / tally.time.as_secs_f64().max(1e-9)
+    );
+    if !tally.failed.is_empty() {
+        println!("Failed: {}", tally.failed.join(" "));
+    }
+    Ok(tally)
+}
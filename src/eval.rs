@@ -1,44 +1,267 @@
+use crate::bitboard::*;
+use crate::endgame::{self, SCALE_NORMAL};
+use crate::king_safety;
+use crate::params::EvalParams;
+use crate::pawns;
+use crate::threats;
 use crabchess::prelude::*;
 
+/// A midgame and an endgame value packed in a single integer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score(i32);
+
+pub const fn s(mg: i32, eg: i32) -> Score {
+    Score((eg << 16).wrapping_add(mg))
+}
+
+impl Score {
+    pub const ZERO: Score = Score(0);
+
+    pub const fn mg(self) -> i32 {
+        self.0 as i16 as i32
+    }
+
+    pub const fn eg(self) -> i32 {
+        ((self.0.wrapping_add(0x8000)) >> 16) as i16 as i32
+    }
+
+    // Blend both values, `phase` going from MAX_PHASE in the opening to 0 in the endgame
+    pub const fn taper(self, phase: i32) -> i32 {
+        (self.mg() * phase + self.eg() * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+
+    // Same as `taper` with the endgame value scaled by `scale` / SCALE_NORMAL
+    pub const fn taper_scaled(self, phase: i32, scale: i32) -> i32 {
+        (self.mg() * phase + self.eg() * (MAX_PHASE - phase) * scale / SCALE_NORMAL) / MAX_PHASE
+    }
+}
+
+impl std::ops::Add for Score {
+    type Output = Score;
+    fn add(self, rhs: Score) -> Score {
+        Score(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl std::ops::AddAssign for Score {
+    fn add_assign(&mut self, rhs: Score) {
+        self.0 = self.0.wrapping_add(rhs.0);
+    }
+}
+
+impl std::ops::Sub for Score {
+    type Output = Score;
+    fn sub(self, rhs: Score) -> Score {
+        Score(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl std::ops::SubAssign for Score {
+    fn sub_assign(&mut self, rhs: Score) {
+        self.0 = self.0.wrapping_sub(rhs.0);
+    }
+}
+
+impl std::ops::Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score(self.0.wrapping_neg())
+    }
+}
+
+impl std::ops::Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, rhs: i32) -> Score {
+        s(self.mg() * rhs, self.eg() * rhs)
+    }
+}
+
 // Piece values
-const PAWN_VALUE: i32 = 100;
-const KNIGHT_VALUE: i32 = 270;
-const BISHOP_VALUE: i32 = 300;
-const ROOK_VALUE: i32 = 500;
-const QUEEN_VALUE: i32 = 900;
-const KING_VALUE: i32 = 0;
-
-// Piece-square tables (white pieces only)
-const PAWN_PST: [i32; 64] = [
+const PAWN_VALUE: Score = s(100, 120);
+const KNIGHT_VALUE: Score = s(270, 280);
+const BISHOP_VALUE: Score = s(300, 310);
+const ROOK_VALUE: Score = s(500, 540);
+const QUEEN_VALUE: Score = s(900, 950);
+const KING_VALUE: Score = s(0, 0);
+
+// Piece-square tables (white pieces only), midgame then endgame
+const PAWN_PST_MG: [i32; 64] = [
     0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 15, 20, 20, 15, 10, 5, 4, 8, 12, 16, 16, 12, 8, 4, 3, 6, 9, 12,
     12, 9, 6, 3, 2, 4, 6, 8, 8, 6, 4, 2, 1, 2, 3, -10, -10, 3, 2, 1, 0, 0, 0, -40, -40, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0,
 ];
+const PAWN_PST_EG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 30, 30, 30, 30, 30, 30, 30, 30, 20, 20, 20, 20, 20, 20, 20, 20, 12, 12,
+    12, 12, 12, 12, 12, 12, 6, 6, 6, 6, 6, 6, 6, 6, 2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
 
-const KNIGHT_PST: [i32; 64] = [
+const KNIGHT_PST_MG: [i32; 64] = [
     -10, -10, -10, -10, -10, -10, -10, -10, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
     -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
     0, 0, 0, 0, 0, -10, -10, -30, -10, -10, -10, -10, -30, -10,
 ];
-const BISHOP_PST: [i32; 64] = [
+const KNIGHT_PST_EG: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
+    0, 0, 0, 0, 0, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+];
+const BISHOP_PST_MG: [i32; 64] = [
     -10, -10, -10, -10, -10, -10, -10, -10, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
     -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
     0, 0, 0, 0, 0, -10, -10, -10, -20, -10, -10, -20, -10, -10,
 ];
-const KING_PST: [i32; 64] = [
+const BISHOP_PST_EG: [i32; 64] = [
+    -10, -10, -10, -10, -10, -10, -10, -10, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
+    0, 0, 0, 0, 0, -10, -10, -10, -10, -10, -10, -10, -10, -10,
+];
+const KING_PST_MG: [i32; 64] = [
     -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40,
     -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -40,
     -40, -40, -40, -40, -40, -40, -40, -40, -40, -40, -20, -20, -20, -20, -20, -20, -20, -20, 0,
     20, 40, -20, 0, -20, 40, 20,
 ];
-
+const ROOK_PST_MG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 5, 10, 10, 10, 10, 10, 10, 5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0,
+    0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, -5, 0, 0, 0, 0, 0, 0, -5, 0, 0,
+    0, 5, 5, 0, 0, 0,
+];
+const ROOK_PST_EG: [i32; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 5, 5, 5, 5, 5, 5, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+const QUEEN_PST_MG: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -5, 0, 5, 5, 5, 5, 0, -5, 0, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10, 0, 5, 0, 0,
+    0, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+];
+const QUEEN_PST_EG: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
+    0, 0, 0, 0, 0, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+];
 // King activity in endgame (reward center)
-const KING_PST_ENDGAME: [i32; 64] = [
+const KING_PST_EG: [i32; 64] = [
     0, 10, 20, 30, 30, 20, 10, 0, 10, 20, 30, 40, 40, 30, 20, 10, 20, 30, 40, 50, 50, 40, 30, 20,
     30, 40, 50, 60, 60, 50, 40, 30, 30, 40, 50, 60, 60, 50, 40, 30, 20, 30, 40, 50, 50, 40, 30, 20,
     10, 20, 30, 40, 40, 30, 20, 10, 0, 10, 20, 30, 30, 20, 10, 0,
 ];
 
+const fn pack(mg: [i32; 64], eg: [i32; 64]) -> [Score; 64] {
+    let mut table = [Score::ZERO; 64];
+    let mut i = 0;
+    while i < 64 {
+        table[i] = s(mg[i], eg[i]);
+        i += 1;
+    }
+    table
+}
+
+pub const PAWN_PST: [Score; 64] = pack(PAWN_PST_MG, PAWN_PST_EG);
+pub const KNIGHT_PST: [Score; 64] = pack(KNIGHT_PST_MG, KNIGHT_PST_EG);
+pub const BISHOP_PST: [Score; 64] = pack(BISHOP_PST_MG, BISHOP_PST_EG);
+pub const ROOK_PST: [Score; 64] = pack(ROOK_PST_MG, ROOK_PST_EG);
+pub const QUEEN_PST: [Score; 64] = pack(QUEEN_PST_MG, QUEEN_PST_EG);
+pub const KING_PST: [Score; 64] = pack(KING_PST_MG, KING_PST_EG);
+
+// Indexed by `bitboard::piece_index`
+pub const PIECE_VALUES: [Score; 6] = [
+    PAWN_VALUE,
+    KNIGHT_VALUE,
+    BISHOP_VALUE,
+    ROOK_VALUE,
+    QUEEN_VALUE,
+    KING_VALUE,
+];
+
+// Mobility, indexed by the number of safe squares attacked
+pub const KNIGHT_MOBILITY: [Score; 9] = [
+    s(-30, -40),
+    s(-12, -19),
+    s(-5, -10),
+    s(1, -3),
+    s(5, 2),
+    s(10, 7),
+    s(13, 12),
+    s(17, 16),
+    s(20, 20),
+];
+pub const BISHOP_MOBILITY: [Score; 14] = [
+    s(-25, -35),
+    s(-7, -13),
+    s(0, -4),
+    s(6, 3),
+    s(11, 9),
+    s(15, 15),
+    s(19, 19),
+    s(23, 24),
+    s(26, 28),
+    s(29, 32),
+    s(32, 35),
+    s(35, 39),
+    s(37, 42),
+    s(40, 45),
+];
+pub const ROOK_MOBILITY: [Score; 15] = [
+    s(-15, -30),
+    s(-6, -7),
+    s(-2, 2),
+    s(1, 9),
+    s(4, 15),
+    s(6, 21),
+    s(8, 26),
+    s(10, 30),
+    s(11, 34),
+    s(13, 38),
+    s(15, 42),
+    s(16, 45),
+    s(17, 49),
+    s(19, 52),
+    s(20, 55),
+];
+pub const QUEEN_MOBILITY: [Score; 28] = [
+    s(-10, -20),
+    s(-3, -7),
+    s(0, -2),
+    s(2, 2),
+    s(3, 5),
+    s(5, 8),
+    s(6, 11),
+    s(8, 13),
+    s(9, 15),
+    s(10, 18),
+    s(11, 20),
+    s(12, 21),
+    s(13, 23),
+    s(14, 25),
+    s(15, 27),
+    s(16, 28),
+    s(17, 30),
+    s(18, 32),
+    s(19, 33),
+    s(19, 35),
+    s(20, 36),
+    s(21, 37),
+    s(22, 39),
+    s(22, 40),
+    s(23, 41),
+    s(24, 43),
+    s(24, 44),
+    s(25, 45),
+];
+
+pub const BISHOP_PAIR_BONUS: Score = s(30, 50);
+// Knight on the opponent's half, protected by a pawn and out of reach of enemy pawns
+pub const KNIGHT_OUTPOST_BONUS: Score = s(25, 15);
+// Per own pawn standing on the bishop's square color
+pub const BAD_BISHOP_PENALTY: Score = s(-2, -5);
+pub const CONNECTED_ROOKS_BONUS: Score = s(10, 5);
+// Rook boxed in by its own king, which can no longer castle to that side
+pub const TRAPPED_ROOK_PENALTY: Score = s(-45, -5);
+// Bishop on a7/h7 cut off by a pawn on b6/g6 (mirrored for black)
+pub const TRAPPED_BISHOP_PENALTY: Score = s(-60, -60);
+
 // Flip for dark pieces
 const FLIP: [usize; 64] = [
     56, 57, 58, 59, 60, 61, 62, 63, 48, 49, 50, 51, 52, 53, 54, 55, 40, 41, 42, 43, 44, 45, 46, 47,
@@ -61,142 +284,499 @@ impl SquareIdx for Square {
     }
 }
 
-// Full evaluation function: material + piece-square tables + rook bonus
-pub fn evaluate(pos: &ChessPosition) -> i32 {
-    let mut score_white = 0;
-    let mut score_black = 0;
+/// Squares attacked by each side, gathered while evaluating mobility
+pub struct Attacks {
+    pub by_piece: [[u64; 6]; 2],
+    pub all: [u64; 2],
+    // squares attacked at least twice
+    pub twice: [u64; 2],
+    // king zone of each side, then how each side attacks the enemy zone
+    pub king_zone: [u64; 2],
+    pub king_attackers: [i32; 2],
+    pub king_attack_weight: [i32; 2],
+    pub king_zone_hits: [i32; 2],
+    attacker_weights: [i32; 6],
+}
 
-    for &file in File::all().iter() {
-        for &rank in Rank::all().iter() {
-            let sq = Square(file, rank);
-            if let Some(piece) = pos.get(sq) {
-                let pst_value = match piece.piece_type {
-                    Type::Pawn => PAWN_PST[sq.to_index()],
-                    Type::Knight => KNIGHT_PST[sq.to_index()],
-                    Type::Bishop => BISHOP_PST[sq.to_index()],
-                    Type::Rook => 0,
-                    Type::Queen => 0,
-                    Type::King => king_value(sq.to_index(), is_endgame(pos)),
-                };
+impl Attacks {
+    fn new(board: &Bitboards, params: &EvalParams) -> Self {
+        let mut attacks = Attacks {
+            by_piece: [[0; 6]; 2],
+            all: [0; 2],
+            twice: [0; 2],
+            king_zone: [WHITE, BLACK].map(|color| king_safety::king_zone(color, board.king(color))),
+            king_attackers: [0; 2],
+            king_attack_weight: [0; 2],
+            king_zone_hits: [0; 2],
+            attacker_weights: params.attacker_weights,
+        };
+        for color in [WHITE, BLACK] {
+            attacks.add(color, PAWN, pawn_attacks(color, board.pieces[color][PAWN]));
+            attacks.add(color, KING, king_attacks(board.king(color)));
+        }
+        attacks
+    }
+
+    fn add(&mut self, color: usize, piece: usize, squares: u64) {
+        self.twice[color] |= self.all[color] & squares;
+        self.all[color] |= squares;
+        self.by_piece[color][piece] |= squares;
+
+        let zone_hits = (squares & self.king_zone[1 - color]).count_ones() as i32;
+        if self.attacker_weights[piece] > 0 && zone_hits > 0 {
+            self.king_attackers[color] += 1;
+            self.king_attack_weight[color] += self.attacker_weights[piece];
+            self.king_zone_hits[color] += zone_hits;
+        }
+    }
+}
 
-                let value = match piece.color {
-                    Color::White => match piece.piece_type {
-                        Type::Pawn => PAWN_VALUE + pst_value,
-                        Type::Knight => KNIGHT_VALUE + pst_value,
-                        Type::Bishop => BISHOP_VALUE + pst_value,
-                        Type::Rook => ROOK_VALUE,
-                        Type::Queen => QUEEN_VALUE,
-                        Type::King => KING_VALUE + pst_value,
-                    },
-                    Color::Black => {
-                        let pst = match piece.piece_type {
-                            Type::Pawn => PAWN_PST[FLIP[sq.to_index()]],
-                            Type::Knight => KNIGHT_PST[FLIP[sq.to_index()]],
-                            Type::Bishop => BISHOP_PST[FLIP[sq.to_index()]],
-                            Type::Rook => 0,
-                            Type::Queen => 0,
-                            Type::King => king_value(FLIP[sq.to_index()], is_endgame(pos)),
-                        };
-                        match piece.piece_type {
-                            Type::Pawn => PAWN_VALUE + pst,
-                            Type::Knight => KNIGHT_VALUE + pst,
-                            Type::Bishop => BISHOP_VALUE + pst,
-                            Type::Rook => ROOK_VALUE,
-                            Type::Queen => QUEEN_VALUE,
-                            Type::King => KING_VALUE + pst,
-                        }
-                    }
+/// Pieces a move takes off and puts on the board, as (color, piece, square) triples
+pub struct MoveDelta {
+    removed: [(usize, usize, usize); 2],
+    removed_len: usize,
+    added: [(usize, usize, usize); 2],
+    added_len: usize,
+}
+
+impl MoveDelta {
+    pub fn new(pos: &ChessPosition, mv: &Move) -> Self {
+        let mut delta = MoveDelta {
+            removed: [(0, 0, 0); 2],
+            removed_len: 0,
+            added: [(0, 0, 0); 2],
+            added_len: 0,
+        };
+        match *mv {
+            Move::Standard {
+                initial_square,
+                piece_type,
+                piece_color,
+                final_square,
+                ..
+            } => {
+                let color = color_index(piece_color);
+                let piece = piece_index(piece_type);
+                delta.capture(pos, final_square);
+                delta.remove(color, piece, initial_square.to_index());
+                delta.add(color, piece, final_square.to_index());
+            }
+            Move::EnPassant {
+                initial_square,
+                capture_square,
+                final_square,
+                piece_color,
+                ..
+            } => {
+                let color = color_index(piece_color);
+                delta.remove(1 - color, PAWN, capture_square.to_index());
+                delta.remove(color, PAWN, initial_square.to_index());
+                delta.add(color, PAWN, final_square.to_index());
+            }
+            Move::Castle { color, side, .. } => {
+                let color = color_index(color);
+                let rank = if color == WHITE { 0 } else { 7 };
+                let (king_to, rook_from, rook_to) = match side {
+                    Side::Kingside => (6, 7, 5),
+                    Side::Queenside => (2, 0, 3),
                 };
+                delta.remove(color, KING, square_at(4, rank));
+                delta.add(color, KING, square_at(king_to, rank));
+                delta.remove(color, ROOK, square_at(rook_from, rank));
+                delta.add(color, ROOK, square_at(rook_to, rank));
+            }
+            Move::PawnPromotion {
+                initial_square,
+                final_square,
+                piece_color,
+                new_type,
+                ..
+            } => {
+                let color = color_index(piece_color);
+                delta.capture(pos, final_square);
+                delta.remove(color, PAWN, initial_square.to_index());
+                delta.add(color, piece_index(new_type.into()), final_square.to_index());
+            }
+        }
+        delta
+    }
+
+    pub fn removed(&self) -> &[(usize, usize, usize)] {
+        &self.removed[..self.removed_len]
+    }
+
+    pub fn added(&self) -> &[(usize, usize, usize)] {
+        &self.added[..self.added_len]
+    }
+
+    fn capture(&mut self, pos: &ChessPosition, sq: Square) {
+        if let Some(victim) = pos.get(sq) {
+            let color = color_index(victim.color);
+            self.remove(color, piece_index(victim.piece_type), sq.to_index());
+        }
+    }
+
+    fn remove(&mut self, color: usize, piece: usize, sq: usize) {
+        self.removed[self.removed_len] = (color, piece, sq);
+        self.removed_len += 1;
+    }
 
-                match piece.color {
-                    Color::White => score_white += value,
-                    Color::Black => score_black += value,
+    fn add(&mut self, color: usize, piece: usize, sq: usize) {
+        self.added[self.added_len] = (color, piece, sq);
+        self.added_len += 1;
+    }
+}
+
+/// Material, piece-square and phase sums of a position, kept up to date as moves are made
+/// so that leaves do not rescan the board for them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Accumulator {
+    // white minus black
+    pub psqt: Score,
+    pub phase: i32,
+}
+
+impl Accumulator {
+    pub fn new(pos: &ChessPosition, params: &EvalParams) -> Self {
+        Self::from_board(&Bitboards::new(pos), params)
+    }
+
+    pub fn from_board(board: &Bitboards, params: &EvalParams) -> Self {
+        let mut acc = Accumulator {
+            psqt: Score::ZERO,
+            phase: 0,
+        };
+        for color in [WHITE, BLACK] {
+            for piece in PAWN..=KING {
+                for sq in squares(board.pieces[color][piece]) {
+                    acc.add(color, piece, sq, params);
                 }
             }
         }
+        acc
     }
 
-    score_white += rook_bonus(pos, Color::White);
-    score_black += rook_bonus(pos, Color::Black);
+    // Accumulator of the position reached by a move
+    pub fn update(&self, delta: &MoveDelta, params: &EvalParams) -> Self {
+        let mut acc = *self;
+        for &(color, piece, sq) in delta.removed() {
+            acc.remove(color, piece, sq, params);
+        }
+        for &(color, piece, sq) in delta.added() {
+            acc.add(color, piece, sq, params);
+        }
+        acc
+    }
 
-    score_white - score_black
+    fn add(&mut self, color: usize, piece: usize, sq: usize, params: &EvalParams) {
+        if color == WHITE {
+            self.psqt += params.piece_values[piece] + params.pst(piece)[sq];
+        } else {
+            self.psqt -= params.piece_values[piece] + params.pst(piece)[FLIP[sq]];
+        }
+        self.phase += PHASE_WEIGHTS[piece];
+    }
+
+    fn remove(&mut self, color: usize, piece: usize, sq: usize, params: &EvalParams) {
+        if color == WHITE {
+            self.psqt -= params.piece_values[piece] + params.pst(piece)[sq];
+        } else {
+            self.psqt += params.piece_values[piece] + params.pst(piece)[FLIP[sq]];
+        }
+        self.phase -= PHASE_WEIGHTS[piece];
+    }
 }
 
-const ROOK_OPEN_FILE_BONUS: i32 = 15;
-const ROOK_SEMI_OPEN_FILE_BONUS: i32 = 10;
-const ROOK_ON_SEVENTH_BONUS: i32 = 20;
+// Full evaluation function: material + piece-square tables + rook bonus + mobility and
+// piece terms + king safety + threats + pawn structure, blended from midgame to endgame
+// values by the game phase, unless endgame knowledge applies. `turn` is the side to move.
+pub fn evaluate(pos: &ChessPosition, turn: Color, params: &EvalParams) -> i32 {
+    evaluate_board(&Bitboards::new(pos), turn, params)
+}
 
-fn rook_bonus(pos: &ChessPosition, color: Color) -> i32 {
-    let mut score = 0;
+// Same as `evaluate` for the bitboards and accumulator the search keeps up to date
+pub fn evaluate_with(
+    board: &Bitboards,
+    turn: Color,
+    acc: &Accumulator,
+    params: &EvalParams,
+) -> i32 {
+    if let Some(eval) = endgame::evaluate(board, color_index(turn), params) {
+        return eval;
+    }
+    let terms = Terms::new(board, params);
+    blend(board, acc.psqt + terms.total(), acc.phase, params)
+}
 
-    for &file in File::all().iter() {
-        for &rank in Rank::all().iter() {
-            let sq = Square(file, rank);
-            if let Some(piece) = pos.get(sq) {
-                if piece.piece_type != Type::Rook || piece.color != color {
-                    continue;
-                }
+// Same as `evaluate` for a position already split into bitboards
+pub fn evaluate_board(board: &Bitboards, turn: Color, params: &EvalParams) -> i32 {
+    if let Some(eval) = endgame::evaluate(board, color_index(turn), params) {
+        return eval;
+    }
+    let acc = Accumulator::from_board(board, params);
+    let terms = Terms::new(board, params);
+    blend(board, acc.psqt + terms.total(), acc.phase, params)
+}
 
-                let file = sq.file();
-                let rank = sq.rank().char().to_digit(10).unwrap() - 1;
-
-                let mut friendly_pawn = false;
-                let mut enemy_pawn = false;
-
-                for r in 0..8 {
-                    let sq_check = Square(file, Rank::from_idx(r).unwrap());
-                    if let Some(p) = pos.get(sq_check) {
-                        if p.piece_type == Type::Pawn {
-                            if p.color == color {
-                                friendly_pawn = true;
-                            } else {
-                                enemy_pawn = true;
-                            }
-                        }
-                    }
-                }
+// Tapered total, the endgame value scaled down in drawish endgames
+fn blend(board: &Bitboards, total: Score, phase: i32, params: &EvalParams) -> i32 {
+    let strong = if total.eg() >= 0 { WHITE } else { BLACK };
+    total.taper_scaled(
+        phase.min(MAX_PHASE),
+        endgame::scale_factor(board, strong, params),
+    )
+}
 
-                if !friendly_pawn && !enemy_pawn {
-                    score += ROOK_OPEN_FILE_BONUS;
-                } else if !friendly_pawn && enemy_pawn {
-                    score += ROOK_SEMI_OPEN_FILE_BONUS;
-                }
+/// Every evaluation term past material and piece-square tables, per side
+pub struct Terms {
+    pub rooks: [Score; 2],
+    pub mobility: [Score; 2],
+    pub king_safety: [Score; 2],
+    pub threats: [Score; 2],
+    pub pawns: [Score; 2],
+}
 
-                if (color == Color::White && rank == 6) || (color == Color::Black && rank == 1) {
-                    score += ROOK_ON_SEVENTH_BONUS;
-                }
+impl Terms {
+    pub fn new(board: &Bitboards, params: &EvalParams) -> Self {
+        let kings = [board.king(WHITE), board.king(BLACK)];
+        let has_pieces = [WHITE, BLACK].map(|color| {
+            board.colors[color] & !board.pieces[color][PAWN] & !board.pieces[color][KING] != 0
+        });
+        let pawn_entry = pawns::probe(
+            [board.pieces[WHITE][PAWN], board.pieces[BLACK][PAWN]],
+            params,
+        );
+
+        // mobility records the attacks king safety and threats are built from
+        let mut attacks = Attacks::new(board, params);
+        let rooks = [WHITE, BLACK].map(|color| rook_bonus(board, &pawn_entry, color, params));
+        let mobility = [WHITE, BLACK].map(|color| pieces(board, color, &mut attacks, params));
+        let king_safety =
+            [WHITE, BLACK].map(|color| king_safety::evaluate_king(board, color, &attacks, params));
+        let threats =
+            [WHITE, BLACK].map(|color| threats::evaluate_threats(board, color, &attacks, params));
+
+        Terms {
+            rooks,
+            mobility,
+            king_safety,
+            threats,
+            pawns: {
+                let passed = pawns::passed_pawns(&pawn_entry, kings, has_pieces, params);
+                [WHITE, BLACK].map(|color| pawn_entry.scores[color] + passed[color])
+            },
+        }
+    }
+
+    // white minus black
+    fn total(&self) -> Score {
+        let mut score = Score::ZERO;
+        for side in [
+            self.rooks,
+            self.pawns,
+            self.mobility,
+            self.king_safety,
+            self.threats,
+        ] {
+            score += side[WHITE] - side[BLACK];
+        }
+        score
+    }
+}
+
+// Per-term breakdown of the evaluation of `pos`, from White's point of view
+pub fn trace(pos: &ChessPosition, turn: Color, params: &EvalParams) -> String {
+    let board = Bitboards::new(pos);
+    let acc = Accumulator::new(pos, params);
+    let phase = acc.phase.min(MAX_PHASE);
+    let terms = Terms::new(&board, params);
+
+    let mut material = [Score::ZERO; 2];
+    let mut pst = [Score::ZERO; 2];
+    for color in [WHITE, BLACK] {
+        for piece in PAWN..=KING {
+            for sq in squares(board.pieces[color][piece]) {
+                let sq = if color == WHITE { sq } else { FLIP[sq] };
+                material[color] += params.piece_values[piece];
+                pst[color] += params.pst(piece)[sq];
             }
         }
     }
 
-    score
+    let rows = [
+        ("Material", material),
+        ("PST", pst),
+        ("Rooks", terms.rooks),
+        ("Pawns", terms.pawns),
+        ("Mobility", terms.mobility),
+        ("King safety", terms.king_safety),
+        ("Threats", terms.threats),
+    ];
+
+    let separator = " ------------+---------------+---------------+-------------------------\n";
+    let mut out = String::new();
+    out.push_str("        Term |     White     |     Black     |           Total\n");
+    out.push_str("             |     MG     EG |     MG     EG |     MG     EG    Tapered\n");
+    out.push_str(separator);
+    let mut total = Score::ZERO;
+    for (name, sides) in rows {
+        let diff = sides[WHITE] - sides[BLACK];
+        let sides = sides.map(|side| format!(" {:>6} {:>6} ", side.mg(), side.eg()));
+        out.push_str(&format!(
+            " {:>11} |{}|{}| {:>6} {:>6} {:>10}\n",
+            name,
+            sides[WHITE],
+            sides[BLACK],
+            diff.mg(),
+            diff.eg(),
+            diff.taper(phase)
+        ));
+        total += diff;
+    }
+    out.push_str(separator);
+    out.push_str(&format!(
+        " {:>11} |{:15}|{:15}| {:>6} {:>6} {:>10}\n",
+        "Total",
+        "",
+        "",
+        total.mg(),
+        total.eg(),
+        total.taper(phase)
+    ));
+    out.push_str(&format!("\nPhase: {} / {}\n", phase, MAX_PHASE));
+    let strong = if total.eg() >= 0 { WHITE } else { BLACK };
+    out.push_str(&format!(
+        "Endgame scale: {} / {}\n",
+        endgame::scale_factor(&board, strong, params),
+        SCALE_NORMAL
+    ));
+    if endgame::evaluate(&board, color_index(turn), params).is_some() {
+        out.push_str("Specialized endgame evaluation\n");
+    }
+    out.push_str(&format!(
+        "Final evaluation: {} (white side)\n",
+        evaluate(pos, turn, params)
+    ));
+    out
 }
 
-fn king_value(square: usize, is_endgame: bool) -> i32 {
-    if is_endgame {
-        KING_PST_ENDGAME[square]
-    } else {
-        KING_PST[square]
+pub const ROOK_OPEN_FILE_BONUS: Score = s(20, 10);
+pub const ROOK_SEMI_OPEN_FILE_BONUS: Score = s(10, 5);
+pub const ROOK_ON_SEVENTH_BONUS: Score = s(15, 30);
+
+fn rook_bonus(
+    board: &Bitboards,
+    pawn_entry: &pawns::PawnEntry,
+    color: usize,
+    params: &EvalParams,
+) -> Score {
+    let mut score = Score::ZERO;
+
+    for sq in squares(board.pieces[color][ROOK]) {
+        let file = 1 << square_file(sq);
+        let friendly_pawn = pawn_entry.pawn_files[color] & file != 0;
+        let enemy_pawn = pawn_entry.pawn_files[1 - color] & file != 0;
+
+        if !friendly_pawn && !enemy_pawn {
+            score += params.rook_open_file_bonus;
+        } else if !friendly_pawn && enemy_pawn {
+            score += params.rook_semi_open_file_bonus;
+        }
+
+        if relative_rank(color, square_rank(sq)) == 6 {
+            score += params.rook_on_seventh_bonus;
+        }
     }
+
+    score
 }
 
-pub fn is_endgame(pos: &ChessPosition) -> bool {
-    let mut material_score = 0;
+// Mobility and piece-specific terms for knights, bishops, rooks and queens of `color`,
+// recording their attacks along the way
+fn pieces(board: &Bitboards, color: usize, attacks: &mut Attacks, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
+    let enemy = 1 - color;
+    let own_pawns = board.pieces[color][PAWN];
+    let enemy_pawns = board.pieces[enemy][PAWN];
+    let king = board.king(color);
+    // squares neither occupied by our own pieces nor attacked by enemy pawns
+    let mobility_area = !board.colors[color] & !pawn_attacks(enemy, enemy_pawns);
 
-    for &file in File::all().iter() {
-        for &rank in Rank::all().iter() {
-            let sq = Square(file, rank);
-            if let Some(piece) = pos.get(sq) {
-                match piece.piece_type {
-                    Type::Pawn | Type::King => {}
-                    Type::Knight => material_score += KNIGHT_VALUE,
-                    Type::Bishop => material_score += BISHOP_VALUE,
-                    Type::Rook => material_score += ROOK_VALUE,
-                    Type::Queen => material_score += QUEEN_VALUE,
-                }
+    for sq in squares(board.pieces[color][KNIGHT]) {
+        let attacked = knight_attacks(sq);
+        attacks.add(color, KNIGHT, attacked);
+        score += params.knight_mobility[(attacked & mobility_area).count_ones() as usize];
+
+        let file = square_file(sq);
+        let rel_rank = relative_rank(color, square_rank(sq));
+        let supported = pawn_attacks(color, own_pawns) & (1 << sq) != 0;
+        let attackable = enemy_pawns & adjacent_files(file) & forward_ranks(color, square_rank(sq));
+        if (3..=5).contains(&rel_rank) && supported && attackable == 0 {
+            score += params.knight_outpost_bonus;
+        }
+    }
+
+    for sq in squares(board.pieces[color][BISHOP]) {
+        let attacked = bishop_attacks(sq, board.occupied);
+        attacks.add(color, BISHOP, attacked);
+        score += params.bishop_mobility[(attacked & mobility_area).count_ones() as usize];
+
+        let same_color = if LIGHT_SQUARES & (1 << sq) != 0 {
+            LIGHT_SQUARES
+        } else {
+            !LIGHT_SQUARES
+        };
+        score += params.bad_bishop_penalty * (own_pawns & same_color).count_ones() as i32;
+
+        let file = square_file(sq);
+        if (file == 0 || file == 7) && relative_rank(color, square_rank(sq)) == 6 {
+            let blocker_file = if file == 0 { 1 } else { 6 };
+            let blocker_rank = if color == WHITE { 5 } else { 2 };
+            if enemy_pawns & (1 << square_at(blocker_file, blocker_rank)) != 0 {
+                score += params.trapped_bishop_penalty;
             }
         }
     }
+    if board.pieces[color][BISHOP].count_ones() >= 2 {
+        score += params.bishop_pair_bonus;
+    }
+
+    let rooks = board.pieces[color][ROOK];
+    let mut connected = false;
+    for sq in squares(rooks) {
+        let attacked = rook_attacks(sq, board.occupied);
+        attacks.add(color, ROOK, attacked);
+        let mobility = (attacked & mobility_area).count_ones();
+        score += params.rook_mobility[mobility as usize];
+
+        connected |= attacked & rooks != 0;
+
+        // king still on its back rank, between the rook and the corner it could have used,
+        // without the right to castle that way and free the rook
+        let king_file = square_file(king);
+        let rook_file = square_file(sq);
+        if mobility <= 3
+            && relative_rank(color, square_rank(king)) == 0
+            && relative_rank(color, square_rank(sq)) == 0
+            && (king_file < 4) == (rook_file < king_file)
+            && !board.can_castle(color, rook_file > king_file)
+        {
+            score += params.trapped_rook_penalty;
+        }
+    }
+    if connected {
+        score += params.connected_rooks_bonus;
+    }
 
-    material_score <= 2000
+    for sq in squares(board.pieces[color][QUEEN]) {
+        let attacked = queen_attacks(sq, board.occupied);
+        attacks.add(color, QUEEN, attacked);
+        score += params.queen_mobility[(attacked & mobility_area).count_ones() as usize];
+    }
+
+    score
 }
+
+// Game phase: 24 with every minor and major piece on the board, 0 with only pawns and kings
+pub const MAX_PHASE: i32 = 24;
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
@@ -0,0 +1,141 @@
+use crate::bitboard::*;
+use crate::eval::{Attacks, Score, s};
+use crate::params::EvalParams;
+
+// Every king safety term is a midgame value only, so it fades out as material comes off
+
+// Own pawn one or two ranks in front of the king, or no pawn at all on that file
+pub const SHIELD_CLOSE_BONUS: Score = s(12, 0);
+pub const SHIELD_FAR_BONUS: Score = s(6, 0);
+pub const SHIELD_MISSING_PENALTY: Score = s(-15, 0);
+// Enemy pawn marching on the king, indexed by its distance in ranks to the king
+pub const PAWN_STORM_PENALTY: [Score; 4] = [s(0, 0), s(-10, 0), s(-25, 0), s(-15, 0)];
+pub const KING_OPEN_FILE_PENALTY: Score = s(-25, 0);
+pub const KING_SEMI_OPEN_FILE_PENALTY: Score = s(-12, 0);
+
+// Attack units of a piece hitting the enemy king zone, indexed by `bitboard::piece_index`
+pub const ATTACKER_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+// Attack units of a safe check available to each piece type
+pub const SAFE_CHECK_UNITS: [i32; 6] = [0, 7, 6, 10, 8, 0];
+pub const ZONE_HIT_UNITS: i32 = 1;
+pub const WEAK_ZONE_SQUARE_UNITS: i32 = 2;
+pub const NO_QUEEN_UNITS: i32 = -8;
+
+// Attack units to centipawns, growing quadratically before leveling out
+pub const KING_DANGER: [i32; 100] = [
+    0, 0, 1, 1, 2, 4, 6, 8, 10, 13, 16, 19, 22, 26, 31, 35, 40, 45, 51, 56, 62, 69, 76, 83, 90, 98,
+    106, 114, 122, 131, 141, 152, 163, 175, 187, 198, 210, 222, 234, 245, 257, 269, 280, 292, 304,
+    316, 327, 339, 351, 362, 374, 386, 397, 409, 421, 432, 444, 456, 468, 479, 491, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+];
+
+// Squares around the king plus the rank in front of them
+pub fn king_zone(color: usize, king: usize) -> u64 {
+    let around = king_attacks(king) | (1 << king);
+    let forward = if color == WHITE {
+        around >> 8
+    } else {
+        around << 8
+    };
+    around | forward
+}
+
+// King safety of `color`, once `attacks` holds every attack of both sides
+pub fn evaluate_king(
+    board: &Bitboards,
+    color: usize,
+    attacks: &Attacks,
+    params: &EvalParams,
+) -> Score {
+    let enemy = 1 - color;
+    let king = board.king(color);
+
+    let mut score = pawn_shelter(board, color, king, params);
+
+    if attacks.king_attackers[enemy] < 2 && board.pieces[enemy][QUEEN] == 0 {
+        return score;
+    }
+
+    let mut units =
+        attacks.king_attack_weight[enemy] + params.zone_hit_units * attacks.king_zone_hits[enemy];
+
+    // zone squares attacked by the enemy and defended by nothing but the king
+    let defended = attacks.all[color] & !(attacks.by_piece[color][KING] & !attacks.twice[color]);
+    let weak = attacks.all[enemy] & attacks.king_zone[color] & !defended;
+    units += params.weak_zone_square_units * weak.count_ones() as i32;
+
+    // checks from squares we do not control
+    let safe = !attacks.all[color] & !board.colors[enemy];
+    let bishop_lines = bishop_attacks(king, board.occupied);
+    let rook_lines = rook_attacks(king, board.occupied);
+    let checks = [
+        (KNIGHT, knight_attacks(king)),
+        (BISHOP, bishop_lines),
+        (ROOK, rook_lines),
+        (QUEEN, bishop_lines | rook_lines),
+    ];
+    for (piece, lines) in checks {
+        if lines & attacks.by_piece[enemy][piece] & safe != 0 {
+            units += params.safe_check_units[piece];
+        }
+    }
+
+    if board.pieces[enemy][QUEEN] == 0 {
+        units += params.no_queen_units;
+    }
+
+    score += s(-params.king_danger[units.clamp(0, 99) as usize], 0);
+    score
+}
+
+// Pawn shield, pawn storm and open files on the king file and its neighbours
+fn pawn_shelter(board: &Bitboards, color: usize, king: usize, params: &EvalParams) -> Score {
+    let mut score = Score::ZERO;
+    let enemy = 1 - color;
+    let king_rank = relative_rank(color, square_rank(king));
+    let in_front = forward_ranks(color, square_rank(king));
+    let center = square_file(king).clamp(1, 6);
+
+    for file in center - 1..=center + 1 {
+        let own = board.pieces[color][PAWN] & file_mask(file);
+        let theirs = board.pieces[enemy][PAWN] & file_mask(file);
+
+        if own == 0 {
+            score += if theirs == 0 {
+                params.king_open_file_penalty
+            } else {
+                params.king_semi_open_file_penalty
+            };
+        }
+
+        let shield = squares(own & in_front)
+            .map(|sq| relative_rank(color, square_rank(sq)))
+            .min();
+        match shield.map(|rank| rank - king_rank) {
+            Some(1) => score += params.shield_close_bonus,
+            Some(2) => score += params.shield_far_bonus,
+            Some(_) => {}
+            None => score += params.shield_missing_penalty,
+        }
+
+        let storm = squares(theirs & in_front)
+            .map(|sq| relative_rank(color, square_rank(sq)))
+            .min();
+        if let Some(rank) = storm {
+            let distance = rank - king_rank;
+            if distance < params.pawn_storm_penalty.len() {
+                // a storming pawn stuck against one of our pawns is less of a threat
+                let blocked = shield.is_some_and(|own_rank| own_rank + 1 == rank);
+                let penalty = params.pawn_storm_penalty[distance];
+                score += if blocked {
+                    s(penalty.mg() / 2, 0)
+                } else {
+                    penalty
+                };
+            }
+        }
+    }
+
+    score
+}
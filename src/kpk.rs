@@ -0,0 +1,165 @@
+use std::sync::OnceLock;
+
+// King and pawn against king bitbase: whether the side with the pawn wins, for every
+// position seen from that side. Squares follow the bitboards, 0 is a8 and 63 is h1.
+
+// Stronger king (64) x weaker king (64) x side to move (2) x pawn on files a to d and
+// ranks 2 to 7 (24), pawns on the other files being mirrored
+const SIZE: usize = 64 * 64 * 2 * 24;
+
+// Classification of a position during the generation, as bit flags so that the results
+// of every move can be combined
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+static BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+// Builds the bitbase ahead of the first probe
+pub fn init() {
+    BITBASE.get_or_init(generate);
+}
+
+// Whether the side with the pawn wins, its pawn moving towards rank 8
+pub fn probe(strong_king: usize, pawn: usize, weak_king: usize, strong_to_move: bool) -> bool {
+    let (strong_king, pawn, weak_king) = if file(pawn) > 3 {
+        (strong_king ^ 7, pawn ^ 7, weak_king ^ 7)
+    } else {
+        (strong_king, pawn, weak_king)
+    };
+    let bits = BITBASE.get_or_init(generate);
+    let idx = index(strong_to_move, strong_king, weak_king, pawn);
+    bits[idx / 64] >> (idx % 64) & 1 != 0
+}
+
+fn file(sq: usize) -> usize {
+    sq % 8
+}
+
+fn rank(sq: usize) -> usize {
+    7 - sq / 8
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    file(a).abs_diff(file(b)).max(rank(a).abs_diff(rank(b)))
+}
+
+fn king_moves(sq: usize) -> impl Iterator<Item = usize> {
+    let (f, r) = (file(sq) as i32, rank(sq) as i32);
+    (-1..=1)
+        .flat_map(move |df| (-1..=1).map(move |dr| (f + df, r + dr)))
+        .filter(move |&(to_f, to_r)| {
+            (to_f, to_r) != (f, r) && (0..8).contains(&to_f) && (0..8).contains(&to_r)
+        })
+        .map(|(to_f, to_r)| (7 - to_r as usize) * 8 + to_f as usize)
+}
+
+fn pawn_attacks(pawn: usize, sq: usize) -> bool {
+    rank(sq) == rank(pawn) + 1 && file(sq).abs_diff(file(pawn)) == 1
+}
+
+fn index(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize) -> usize {
+    strong_king
+        | weak_king << 6
+        | (!strong_to_move as usize) << 12
+        | file(pawn) << 13
+        | (6 - rank(pawn)) << 15
+}
+
+// Inverse of `index`: side to move, stronger king, weaker king and pawn
+fn decode(idx: usize) -> (bool, usize, usize, usize) {
+    let pawn_rank = 6 - (idx >> 15);
+    let pawn = (7 - pawn_rank) * 8 + (idx >> 13 & 3);
+    (idx >> 12 & 1 == 0, idx & 63, idx >> 6 & 63, pawn)
+}
+
+// Results that need no search: impossible positions, safe promotions, stalemates and
+// captures of an undefended pawn
+fn initial(idx: usize) -> u8 {
+    let (strong_to_move, strong_king, weak_king, pawn) = decode(idx);
+    let push = pawn - 8;
+
+    if distance(strong_king, weak_king) <= 1
+        || strong_king == pawn
+        || weak_king == pawn
+        || strong_to_move && pawn_attacks(pawn, weak_king)
+    {
+        INVALID
+    } else if strong_to_move
+        && rank(pawn) == 6
+        && strong_king != push
+        && (distance(weak_king, push) > 1 || distance(strong_king, push) == 1)
+    {
+        WIN
+    } else if !strong_to_move
+        && (king_moves(weak_king)
+            .all(|sq| distance(sq, strong_king) <= 1 || pawn_attacks(pawn, sq))
+            || distance(weak_king, pawn) == 1 && distance(strong_king, pawn) > 1)
+    {
+        DRAW
+    } else {
+        UNKNOWN
+    }
+}
+
+// A position is won with the stronger side to move when one move leads to a win, and
+// drawn with the weaker side to move when one move leads to a draw
+fn classify(idx: usize, db: &[u8]) -> u8 {
+    let (strong_to_move, strong_king, weak_king, pawn) = decode(idx);
+    let (good, bad) = if strong_to_move {
+        (WIN, DRAW)
+    } else {
+        (DRAW, WIN)
+    };
+
+    let mut results = INVALID;
+    if strong_to_move {
+        for sq in king_moves(strong_king) {
+            results |= db[index(false, sq, weak_king, pawn)];
+        }
+        let push = pawn - 8;
+        if rank(pawn) < 6 {
+            results |= db[index(false, strong_king, weak_king, push)];
+        }
+        if rank(pawn) == 1 && push != strong_king && push != weak_king {
+            results |= db[index(false, strong_king, weak_king, push - 8)];
+        }
+    } else {
+        for sq in king_moves(weak_king) {
+            results |= db[index(true, strong_king, sq, pawn)];
+        }
+    }
+
+    if results & good != 0 {
+        good
+    } else if results & UNKNOWN != 0 {
+        UNKNOWN
+    } else {
+        bad
+    }
+}
+
+// Retrograde analysis: classify positions from their successors until nothing changes,
+// whatever is still unknown then being a draw
+fn generate() -> Vec<u64> {
+    let mut db: Vec<u8> = (0..SIZE).map(initial).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..SIZE {
+            if db[idx] == UNKNOWN {
+                db[idx] = classify(idx, &db);
+                changed |= db[idx] != UNKNOWN;
+            }
+        }
+    }
+
+    let mut bits = vec![0u64; SIZE / 64];
+    for (idx, &result) in db.iter().enumerate() {
+        if result == WIN {
+            bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+    bits
+}
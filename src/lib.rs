@@ -0,0 +1,20 @@
+pub mod bench;
+pub mod bitboard;
+pub mod book;
+pub mod datagen;
+pub mod endgame;
+pub mod epd;
+pub mod eval;
+pub mod king_safety;
+pub mod kpk;
+pub mod moves;
+pub mod nnue;
+pub mod params;
+pub mod pawns;
+pub mod pgn;
+pub mod san;
+pub mod search;
+pub mod threats;
+pub mod tune;
+pub mod uci;
+pub mod zobrist;
@@ -1,16 +1,21 @@
-mod eval;
-mod moves;
-mod search;
-mod uci;
-mod zobrist;
-
+use book::Book;
+use copperfish::{
+    bench, bitboard, book, datagen, epd, eval, kpk, moves, nnue, params, pawns, san, search, tune,
+    uci, zobrist,
+};
 use crabchess::prelude::*;
 use eval::*;
+use moves::{generate_legal_moves, halfmove_clock, is_irreversible};
+use nnue::Network;
+use params::EvalParams;
 use search::*;
+use std::env;
+use std::fs;
 use std::io::{self, BufRead, Write};
+use std::process;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
     Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
 };
 use std::thread;
 use std::time::{Duration, Instant};
@@ -18,10 +23,110 @@ use uci::*;
 use zobrist::Zobrist;
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    kpk::init();
+
+    let mut params = EvalParams::default();
+    let mut network = None;
+    if let Some(i) = args.iter().position(|arg| arg == "--eval-file") {
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("--eval-file needs a path");
+            process::exit(1);
+        };
+        match load_eval_file(path) {
+            Ok(EvalFile::Params(loaded)) => params = *loaded,
+            Ok(EvalFile::Network(loaded)) => network = Some(loaded),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        args.drain(i..=i + 1);
+    }
+
+    match args.first().map(String::as_str) {
+        None => uci_loop(params, network),
+        Some("dump-params") => print!("{}", params.to_toml()),
+        Some("tune") => {
+            if let Err(e) =
+                tune::TuneOptions::parse(&args[1..]).and_then(|options| tune::run(&options, params))
+            {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("datagen") => {
+            if let Err(e) = datagen::DatagenOptions::parse(&args[1..])
+                .and_then(|options| datagen::run(&options, params, network))
+            {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("bench") => match args.get(1).map(|depth| depth.parse()).transpose() {
+            Ok(depth) => bench::run(
+                depth.unwrap_or(bench::DEFAULT_DEPTH),
+                &params,
+                network.as_ref(),
+            ),
+            Err(e) => {
+                eprintln!("bench depth: {}", e);
+                process::exit(1);
+            }
+        },
+        Some("epd") => {
+            if let Err(e) = epd::EpdOptions::parse(&args[1..])
+                .and_then(|options| epd::run(&options, params, network))
+            {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some("book") if args.get(1).map(String::as_str) == Some("build") => {
+            if let Err(e) =
+                book::BuildOptions::parse(&args[2..]).and_then(|options| book::build(&options))
+            {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Some(command) => {
+            eprintln!("Unknown command: {}", command);
+            process::exit(1);
+        }
+    }
+}
+
+/// Contents of the file given through `--eval-file` or the EvalFile option
+enum EvalFile {
+    Params(Box<EvalParams>),
+    Network(Network),
+}
+
+// Networks are recognized by their .nnue extension, anything else is a parameter file
+fn load_eval_file(path: &str) -> Result<EvalFile, String> {
+    if path.ends_with(".nnue") {
+        Network::load(path).map(EvalFile::Network)
+    } else {
+        EvalParams::load(path).map(|params| EvalFile::Params(Box::new(params)))
+    }
+}
+
+fn uci_loop(params: EvalParams, network: Option<Network>) {
+    let mut params = Arc::new(params);
+    let mut use_nnue = network.is_some();
+    let mut network = network.map(Arc::new);
     let mut zobrist = Arc::new(Mutex::new(Zobrist::new()));
     let mut tt = Arc::new(Mutex::new(TranspositionTable::new()));
     let mut position = ChessPosition::new();
     let mut turn = Color::White;
+    let mut history = History::new(zobrist.lock().unwrap().hash_position(&position, turn), 0);
+    let mut contempt = Contempt::new();
+    let mut book: Option<Book> = None;
+    let mut own_book = false;
+    let mut book_depth = 20;
+    let mut book_best_move = false;
+    let mut san_pv = false;
 
     let stdin = io::stdin();
     let mut stdout = io::stdout();
@@ -33,39 +138,141 @@ fn main() {
             Some("uci") => {
                 println!("id name Copperfish");
                 println!("id author Nicolas Duhamel");
+                println!("option name Contempt type spin default 0 min -200 max 200");
+                println!("option name ContemptScaling type check default true");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name UseNNUE type check default {}", use_nnue);
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                println!("option name BookDepth type spin default 20 min 1 max 200");
+                println!("option name BookBestMove type check default false");
+                println!("option name SanPV type check default false");
                 println!("uciok");
             }
             Some("isready") => {
                 println!("readyok");
             }
+            Some("setoption") => match parse_setoption(parts) {
+                Some((name, value)) if name.eq_ignore_ascii_case("Contempt") => {
+                    if let Ok(value) = value.parse::<i32>() {
+                        contempt.value = value.clamp(-200, 200);
+                    }
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("ContemptScaling") => {
+                    contempt.scale_by_phase = value == "true";
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("EvalFile") => {
+                    let loaded = if value.is_empty() || value == "<empty>" {
+                        Ok(EvalFile::Params(Box::default()))
+                    } else {
+                        load_eval_file(&value)
+                    };
+                    match loaded {
+                        Ok(EvalFile::Params(loaded)) => {
+                            params = Arc::new(*loaded);
+                            pawns::clear();
+                        }
+                        Ok(EvalFile::Network(loaded)) => {
+                            println!(
+                                "info string Loaded network {} with {} hidden neurons",
+                                value,
+                                loaded.hidden_size()
+                            );
+                            network = Some(Arc::new(loaded));
+                        }
+                        Err(e) => println!("info string {}", e),
+                    }
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("UseNNUE") => {
+                    use_nnue = value == "true";
+                    if use_nnue && network.is_none() {
+                        println!("info string No network loaded, set EvalFile to a .nnue file");
+                    }
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("OwnBook") => {
+                    own_book = value == "true";
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("BookFile") => {
+                    book = None;
+                    if !value.is_empty() && value != "<empty>" {
+                        match Book::load(&value) {
+                            Ok(loaded) => {
+                                println!(
+                                    "info string Loaded book {} with {} entries",
+                                    value,
+                                    loaded.len()
+                                );
+                                book = Some(loaded);
+                            }
+                            Err(e) => println!("info string {}", e),
+                        }
+                    }
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("BookDepth") => {
+                    if let Ok(value) = value.parse::<usize>() {
+                        book_depth = value.clamp(1, 200);
+                    }
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("BookBestMove") => {
+                    book_best_move = value == "true";
+                }
+                Some((name, value)) if name.eq_ignore_ascii_case("SanPV") => {
+                    san_pv = value == "true";
+                }
+                _ => {}
+            },
             Some("ucinewgame") => {
                 zobrist = Arc::new(Mutex::new(Zobrist::new()));
                 tt = Arc::new(Mutex::new(TranspositionTable::new()));
                 position = ChessPosition::new();
                 turn = Color::White;
+                history = History::new(zobrist.lock().unwrap().hash_position(&position, turn), 0);
             }
-            Some("position") => {
-                while let Some(token) = parts.next() {
-                    if token == "startpos" {
-                        position = ChessPosition::new();
-                        turn = Color::White;
-                    } else if token == "moves" {
-                        while let Some(mv_str) = parts.next() {
-                            if let Some(mv) = move_from_uci(&position, mv_str) {
-                                position.apply_move(mv).unwrap();
-                                turn = turn.other();
-                            }
-                        }
+            Some("position") => match PositionCommand::parse(parts) {
+                Ok(command) => {
+                    let zob = zobrist.lock().unwrap();
+                    position = command.start;
+                    turn = command.turn;
+                    history = History::new(
+                        zob.hash_position(&position, turn),
+                        halfmove_clock(&position),
+                    );
+                    for mv in command.moves {
+                        let irreversible = is_irreversible(&position, &mv);
+                        position.apply_move(mv).unwrap();
+                        turn = turn.other();
+                        history.push(zob.hash_position(&position, turn), irreversible);
+                    }
+                    if let Some(e) = command.error {
+                        println!("info string {}", e);
                     }
                 }
-            }
+                Err(e) => println!("info string {}", e),
+            },
             Some("go") => {
+                // the book is consulted first, the search only runs once out of it
+                let book_move = book
+                    .as_ref()
+                    .filter(|_| own_book)
+                    .and_then(|book| book.pick(&position, turn, book_depth, book_best_move));
+                if let Some(book_move) = book_move {
+                    println!("info string Book move");
+                    println!("bestmove {}", book_move.uci());
+                    stdout.flush().unwrap();
+                    continue;
+                }
+
                 let best_move = search_with_time(
                     position.clone(),
                     turn,
-                    Duration::from_millis(2990),
+                    SearchLimits::parse(parts, turn),
                     Arc::clone(&zobrist),
                     Arc::clone(&tt),
+                    history.clone(),
+                    contempt,
+                    Arc::clone(&params),
+                    network.clone().filter(|_| use_nnue),
+                    san_pv,
                 );
                 if let Some(best_move) = best_move {
                     println!("bestmove {}", best_move.uci());
@@ -78,6 +285,33 @@ fn main() {
                     };
                 }
             }
+            Some("bench") => {
+                let depth = parts.next().and_then(|depth| depth.parse().ok());
+                bench::run(
+                    depth.unwrap_or(bench::DEFAULT_DEPTH),
+                    &params,
+                    network.as_deref().filter(|_| use_nnue),
+                );
+            }
+            Some("d") => print!("{}", display(&position, turn, &zobrist.lock().unwrap())),
+            Some("eval") => {
+                print!("{}", trace(&position, turn, &params));
+                if let Some(network) = &network {
+                    let acc = network.refresh(&bitboard::Bitboards::new(&position));
+                    println!(
+                        "NNUE evaluation: {} (white side)",
+                        network.evaluate(&acc, turn)
+                    );
+                }
+            }
+            Some("dump-params") => match parts.next() {
+                Some(path) => {
+                    if let Err(e) = fs::write(path, params.to_toml()) {
+                        println!("info string {}: {}", path, e);
+                    }
+                }
+                None => print!("{}", params.to_toml()),
+            },
             Some("quit") => {
                 break;
             }
@@ -87,12 +321,51 @@ fn main() {
     }
 }
 
+// Board, FEN, hash key and legal moves of the current position, for the `d` command
+fn display(position: &ChessPosition, turn: Color, zob: &Zobrist) -> String {
+    let fen = position.fen();
+    let separator = " +---+---+---+---+---+---+---+---+\n";
+    let mut out = String::from(separator);
+    for (row, rank) in fen
+        .split_whitespace()
+        .next()
+        .unwrap()
+        .split('/')
+        .enumerate()
+    {
+        out.push(' ');
+        for c in rank.chars() {
+            match c.to_digit(10) {
+                Some(empty) => out += &"|   ".repeat(empty as usize),
+                None => out += &format!("| {} ", c),
+            }
+        }
+        out += &format!("| {}\n{}", 8 - row, separator);
+    }
+    out += "   a   b   c   d   e   f   g   h\n\n";
+
+    let moves: Vec<String> = generate_legal_moves(position, turn, None, &[None; 2])
+        .iter()
+        .map(|mv| san::san(position, turn, mv))
+        .collect();
+    out += &format!("Fen: {}\n", fen);
+    out += &format!("Key: {:016X}\n", zob.hash_position(position, turn));
+    out += &format!("Legal moves: {}\n", moves.join(" "));
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
 fn search_with_time(
     position: ChessPosition,
     turn: Color,
-    max_time: Duration,
+    limits: SearchLimits,
     zob: Arc<Mutex<Zobrist>>,
     tt: Arc<Mutex<TranspositionTable>>,
+    mut history: History,
+    contempt: Contempt,
+    params: Arc<EvalParams>,
+    network: Option<Arc<Network>>,
+    san_pv: bool,
 ) -> Option<Move> {
     let tt_clone = Arc::clone(&tt);
     let zob_clone = Arc::clone(&zob);
@@ -105,53 +378,88 @@ fn search_with_time(
     // Spawn search thread
     thread::spawn(move || {
         let mut tt = tt_clone.lock().unwrap();
-        let mut zob = zob_clone.lock().unwrap();
-        let hash = zob.hash_position(&position, turn);
+        let zob = zob_clone.lock().unwrap();
+        let hash = history.hash();
         let (mut guess, depth_start) = if let Some(entry) = tt.get(&hash) {
             (entry.value, entry.depth.max(3) - 2)
         } else {
-            (evaluate(&position), 1)
+            (evaluate(&position, turn, &params), 1)
         };
+        let mut searcher = Searcher::new(
+            &mut tt,
+            &zob,
+            &params,
+            network.as_deref(),
+            &mut history,
+            contempt,
+            &stop_flag_clone,
+        );
+        searcher.node_limit = limits.nodes;
 
-        for depth in (depth_start..MAX_DEPTH).step_by(2) {
-            let (mv, score) = aspiration_search(
-                &position,
-                turn,
-                guess,
-                depth,
-                25,
-                &mut tt,
-                &mut zob,
-                &stop_flag_clone,
-            );
+        // a mate in n moves needs 2n - 1 plies to be seen
+        let mut max_depth = limits.depth.unwrap_or(MAX_PLY - 1);
+        if let Some(n) = limits.mate {
+            max_depth = max_depth.min(2 * n.max(1) as usize - 1);
+        }
+        let max_depth = max_depth.clamp(1, MAX_PLY - 1);
+
+        // a deeper table entry from an earlier search must not skip the requested depth
+        for depth in depth_start.min(max_depth)..=max_depth {
+            let result = searcher.aspiration_search(&position, turn, guess, depth, 25);
 
+            let Some((mv, score)) = result else {
+                break; // stopped before any root move was searched
+            };
             if stop_flag_clone.load(Ordering::Relaxed) {
                 break; // exit immediately if time is up
             }
 
             *best_move_clone.lock().unwrap() = Some(mv);
             guess = score;
+            let pv = searcher.pv(&position, turn, mv, depth);
+            let pv_uci: Vec<String> = pv.iter().map(|mv| mv.uci()).collect();
             let sign = if turn == Color::White { 1 } else { -1 };
-            if score.abs() > WHITE_MATE - MAX_DEPTH as i32 {
+            if let Some(moves) = mate_in(sign * score) {
                 println!(
-                    "info depth {} score mate {}",
+                    "info depth {} seldepth {} nodes {} score mate {} pv {}",
                     depth,
-                    sign * (WHITE_MATE - score.abs() + 1)
+                    searcher.seldepth,
+                    searcher.nodes,
+                    moves,
+                    pv_uci.join(" ")
                 );
-                stop_flag_clone.store(true, Ordering::Relaxed); // forced mate found, stop search
-                break;
+                if san_pv {
+                    println!("info string pv {}", san::line(&position, turn, &pv));
+                }
+                if limits.mate.is_none_or(|n| moves <= n) {
+                    break; // forced mate found, stop search
+                }
+                continue;
+            }
+            println!(
+                "info depth {} seldepth {} nodes {} score cp {} pv {}",
+                depth,
+                searcher.seldepth,
+                searcher.nodes,
+                sign * score,
+                pv_uci.join(" ")
+            );
+            if san_pv {
+                println!("info string pv {}", san::line(&position, turn, &pv));
             }
-            println!("info depth {} score cp {}", depth, sign * score);
         }
+        stop_flag_clone.store(true, Ordering::Relaxed); // search over, no need to wait
     });
 
     // Main thread: monitor time
     let start = Instant::now();
-    while start.elapsed() < max_time && !stop_flag.load(Ordering::Relaxed) {
+    while limits.time.is_none_or(|time| start.elapsed() < time)
+        && !stop_flag.load(Ordering::Relaxed)
+    {
         thread::sleep(Duration::from_millis(10));
     }
     stop_flag.store(true, Ordering::Relaxed); // signal thread to stop
 
     // Return best move
-    best_move.lock().unwrap().clone()
+    *best_move.lock().unwrap()
 }
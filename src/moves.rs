@@ -13,10 +13,10 @@ pub fn generate_captures(pos: &ChessPosition, turn: Color) -> Vec<Move> {
                 }
                 match mv {
                     Move::Standard { final_square, .. } if pos.get(final_square).is_none() => {
-                        continue
+                        continue;
                     }
                     Move::PawnPromotion { final_square, .. } if pos.get(final_square).is_none() => {
-                        continue
+                        continue;
                     }
                     Move::Castle { .. } => continue,
                     _ => {}
@@ -41,14 +41,16 @@ pub fn generate_legal_moves(
         for &rank in Rank::all().iter() {
             let sq = Square(file, rank);
 
-            // moves to an empty square
-            for mv in pos.pseudolegally_navigable(sq, turn) {
-                candidates.push(mv);
-            }
-
-            // threats / captures to this square
-            for mv in pos.pseudolegal_threats(sq, turn.other()) {
-                candidates.push(mv);
+            // moves to an empty square, then threats / captures to this square. En passant
+            // shows up both as a move behind the pawn and as a threat to it, keep it once.
+            let moves = pos
+                .pseudolegally_navigable(sq, turn)
+                .into_iter()
+                .chain(pos.pseudolegal_threats(sq, turn.other()));
+            for mv in moves {
+                if !matches!(mv, Move::EnPassant { .. }) || !candidates.contains(&mv) {
+                    candidates.push(mv);
+                }
             }
         }
     }
@@ -81,10 +83,10 @@ pub fn generate_legal_moves(
         if mv.needs_promotion() {
             if let Some(opts) = mv.promotion_options(pos) {
                 for ptype in opts {
-                    if let Ok(prom) = mv.to_promotion(ptype) {
-                        if prom.is_legal(pos).unwrap_or(false) {
-                            legal.push(prom);
-                        }
+                    if let Ok(prom) = mv.to_promotion(ptype)
+                        && prom.is_legal(pos).unwrap_or(false)
+                    {
+                        legal.push(prom);
                     }
                 }
             }
@@ -98,6 +100,20 @@ pub fn generate_legal_moves(
     legal
 }
 
+// Captures and pawn moves can never be undone, so no earlier position can repeat
+pub fn is_irreversible(pos: &ChessPosition, mv: &Move) -> bool {
+    mv.piece_type() == Type::Pawn || mv.is_capture() || pos.get(mv.final_square()).is_some()
+}
+
+// Plies since the last capture or pawn move, as counted in the FEN of the position
+pub fn halfmove_clock(pos: &ChessPosition) -> u16 {
+    pos.fen()
+        .split_whitespace()
+        .nth(4)
+        .and_then(|clock| clock.parse().ok())
+        .unwrap_or(0)
+}
+
 // Time optimization to reduce the tree search
 // Most Valuable Victim, Least Valuable Aggressor
 // MVV-LVA[victim][attacker]
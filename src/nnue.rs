@@ -0,0 +1,243 @@
+use crate::bitboard::*;
+use crate::eval::MoveDelta;
+use crabchess::prelude::*;
+use std::fs;
+
+// Quantization of the feature transformer and of the output layer, and the factor turning
+// the network output into centipawns
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+// One input per (side relative to the perspective, piece type, square)
+const INPUTS: usize = 768;
+// Hidden neurons are processed 16 at a time by the SIMD code
+const HIDDEN_ALIGN: usize = 16;
+
+/// A 768 -> N -> 1 perspective network with a clipped ReLU activation.
+///
+/// The file is a flat list of little-endian i16, optionally zero-padded at the end:
+/// the feature weights (768 rows of N), the N feature biases, the 2N output weights
+/// (side to move accumulator first) and the output bias. Inputs index our pieces then
+/// theirs, pawn to king, squares from a1 to h8, seen from Black's side for Black.
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+/// Hidden layer values of a position from each side's perspective, indexed by color
+#[derive(Clone, Debug, PartialEq)]
+pub struct NnueAccumulator {
+    values: [Vec<i16>; 2],
+}
+
+impl Network {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let values: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+
+        // each hidden neuron has 768 weights, a bias and two output weights
+        let per_neuron = INPUTS + 3;
+        let hidden = values.len().saturating_sub(1) / per_neuron;
+        let used = hidden * per_neuron + 1;
+        if hidden == 0 || !hidden.is_multiple_of(HIDDEN_ALIGN) || values.len() - used >= 32 {
+            return Err(format!(
+                "not a 768 -> N -> 1 network with N a multiple of {} ({} bytes)",
+                HIDDEN_ALIGN,
+                bytes.len()
+            ));
+        }
+
+        let (feature_weights, rest) = values.split_at(INPUTS * hidden);
+        let (feature_bias, rest) = rest.split_at(hidden);
+        let (output_weights, rest) = rest.split_at(2 * hidden);
+        Ok(Network {
+            hidden,
+            feature_weights: feature_weights.to_vec(),
+            feature_bias: feature_bias.to_vec(),
+            output_weights: output_weights.to_vec(),
+            output_bias: rest[0] as i32,
+        })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    // Accumulator of `board` computed from scratch
+    pub fn refresh(&self, board: &Bitboards) -> NnueAccumulator {
+        let mut acc = NnueAccumulator {
+            values: [self.feature_bias.clone(), self.feature_bias.clone()],
+        };
+        for perspective in [WHITE, BLACK] {
+            for color in [WHITE, BLACK] {
+                for piece in PAWN..=KING {
+                    for sq in squares(board.pieces[color][piece]) {
+                        let row = self.row(feature(perspective, color, piece, sq));
+                        for (value, weight) in acc.values[perspective].iter_mut().zip(row) {
+                            *value = value.wrapping_add(*weight);
+                        }
+                    }
+                }
+            }
+        }
+        acc
+    }
+
+    // Accumulator after a move, from the accumulator of the position it was played in
+    pub fn update(&self, parent: &NnueAccumulator, child: &mut NnueAccumulator, delta: &MoveDelta) {
+        for perspective in [WHITE, BLACK] {
+            // a move adds and removes at most two pieces each
+            let rows = |changes: &[(usize, usize, usize)]| {
+                let mut rows: [&[i16]; 2] = [&[], &[]];
+                for (row, &(color, piece, sq)) in rows.iter_mut().zip(changes) {
+                    *row = self.row(feature(perspective, color, piece, sq));
+                }
+                rows
+            };
+            let added = rows(delta.added());
+            let removed = rows(delta.removed());
+            simd::apply_rows(
+                &parent.values[perspective],
+                &mut child.values[perspective],
+                &added[..delta.added().len()],
+                &removed[..delta.removed().len()],
+            );
+        }
+    }
+
+    // Evaluation in centipawns from White's point of view, `turn` being the side to move
+    pub fn evaluate(&self, acc: &NnueAccumulator, turn: Color) -> i32 {
+        let (us, them) = if turn == Color::White {
+            (WHITE, BLACK)
+        } else {
+            (BLACK, WHITE)
+        };
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let sum = simd::crelu_dot(&acc.values[us], our_weights)
+            + simd::crelu_dot(&acc.values[them], their_weights);
+        // a large network output times SCALE does not fit in an i32
+        let eval = (sum as i64 + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64;
+        let eval = eval.clamp(i32::MIN as i64 + 1, i32::MAX as i64) as i32;
+
+        if turn == Color::White { eval } else { -eval }
+    }
+
+    fn row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+}
+
+impl NnueAccumulator {
+    // Placeholder for the search stack, overwritten before it is read
+    pub fn empty(hidden: usize) -> Self {
+        NnueAccumulator {
+            values: [vec![0; hidden], vec![0; hidden]],
+        }
+    }
+}
+
+// Input index of a `color` piece on `sq` seen from `perspective`
+fn feature(perspective: usize, color: usize, piece: usize, sq: usize) -> usize {
+    // our square indices start from a8, the network counts from a1 of its own side
+    let sq = if perspective == WHITE { sq ^ 56 } else { sq };
+    let side = if color == perspective { 0 } else { 1 };
+    side * 384 + piece * 64 + sq
+}
+
+mod simd {
+    use super::QA;
+
+    // child = parent + every added row - every removed row
+    pub fn apply_rows(parent: &[i16], child: &mut [i16], added: &[&[i16]], removed: &[&[i16]]) {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: avx2 is available on this CPU
+            unsafe { apply_rows_avx2(parent, child, added, removed) };
+            return;
+        }
+
+        child.copy_from_slice(parent);
+        for row in added {
+            for (value, weight) in child.iter_mut().zip(row.iter()) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+        for row in removed {
+            for (value, weight) in child.iter_mut().zip(row.iter()) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+
+    // Sum of clamp(value, 0, QA) * weight
+    pub fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: avx2 is available on this CPU
+            return unsafe { crelu_dot_avx2(values, weights) };
+        }
+
+        values
+            .iter()
+            .zip(weights)
+            .map(|(&value, &weight)| (value as i32).clamp(0, QA) * weight as i32)
+            .sum()
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    fn apply_rows_avx2(parent: &[i16], child: &mut [i16], added: &[&[i16]], removed: &[&[i16]]) {
+        use std::arch::x86_64::*;
+
+        for i in (0..parent.len()).step_by(16) {
+            // SAFETY: every slice holds the hidden layer size, a multiple of 16, of values
+            unsafe {
+                let mut v = _mm256_loadu_si256(parent.as_ptr().add(i).cast());
+                for row in added {
+                    v = _mm256_add_epi16(v, _mm256_loadu_si256(row.as_ptr().add(i).cast()));
+                }
+                for row in removed {
+                    v = _mm256_sub_epi16(v, _mm256_loadu_si256(row.as_ptr().add(i).cast()));
+                }
+                _mm256_storeu_si256(child.as_mut_ptr().add(i).cast(), v);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    fn crelu_dot_avx2(values: &[i16], weights: &[i16]) -> i32 {
+        use std::arch::x86_64::*;
+
+        let zero = _mm256_setzero_si256();
+        let qa = _mm256_set1_epi16(QA as i16);
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..values.len()).step_by(16) {
+            // SAFETY: both slices hold the hidden layer size, a multiple of 16, of values
+            let (v, w) = unsafe {
+                (
+                    _mm256_loadu_si256(values.as_ptr().add(i).cast()),
+                    _mm256_loadu_si256(weights.as_ptr().add(i).cast()),
+                )
+            };
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(v, zero), qa);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+        }
+
+        let mut lanes = [0i32; 8];
+        // SAFETY: `lanes` is 32 bytes long
+        unsafe { _mm256_storeu_si256(lanes.as_mut_ptr().cast(), sum) };
+        lanes.iter().sum()
+    }
+}
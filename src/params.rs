@@ -0,0 +1,228 @@
+use crate::bitboard::*;
+use crate::eval::{self, Score, s};
+use crate::{king_safety, pawns, threats};
+
+// Values that can be written to and read back from a TOML parameter file, or flattened
+// into plain integers for tuning
+trait Param: Sized {
+    fn to_toml(&self) -> String;
+    fn from_toml(value: &toml::Value) -> Result<Self, String>;
+    fn flatten(&self, out: &mut Vec<i32>);
+    fn unflatten(values: &mut impl Iterator<Item = i32>) -> Self;
+}
+
+impl Param for i32 {
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+
+    fn flatten(&self, out: &mut Vec<i32>) {
+        out.push(*self);
+    }
+
+    fn unflatten(values: &mut impl Iterator<Item = i32>) -> Self {
+        values.next().unwrap()
+    }
+
+    fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        value
+            .as_integer()
+            .and_then(|v| i32::try_from(v).ok())
+            .ok_or_else(|| format!("expected an integer, got {}", value))
+    }
+}
+
+// Written as an [mg, eg] pair
+impl Param for Score {
+    fn to_toml(&self) -> String {
+        format!("[{}, {}]", self.mg(), self.eg())
+    }
+
+    fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        let [mg, eg] = <[i32; 2]>::from_toml(value)?;
+        Ok(s(mg, eg))
+    }
+
+    fn flatten(&self, out: &mut Vec<i32>) {
+        out.extend([self.mg(), self.eg()]);
+    }
+
+    fn unflatten(values: &mut impl Iterator<Item = i32>) -> Self {
+        let [mg, eg] = <[i32; 2]>::unflatten(values);
+        s(mg, eg)
+    }
+}
+
+// Long tables are wrapped eight values per line
+impl<T: Param, const N: usize> Param for [T; N] {
+    fn to_toml(&self) -> String {
+        let values: Vec<String> = self.iter().map(Param::to_toml).collect();
+        if N <= 8 {
+            return format!("[{}]", values.join(", "));
+        }
+        let mut out = String::from("[\n");
+        for line in values.chunks(8) {
+            out.push_str(&format!("    {},\n", line.join(", ")));
+        }
+        out.push(']');
+        out
+    }
+
+    fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        let array = value
+            .as_array()
+            .ok_or_else(|| format!("expected an array, got {}", value))?;
+        let values = array
+            .iter()
+            .map(T::from_toml)
+            .collect::<Result<Vec<T>, String>>()?;
+        let len = values.len();
+        values
+            .try_into()
+            .map_err(|_| format!("expected {} values, got {}", N, len))
+    }
+
+    fn flatten(&self, out: &mut Vec<i32>) {
+        for value in self {
+            value.flatten(out);
+        }
+    }
+
+    fn unflatten(values: &mut impl Iterator<Item = i32>) -> Self {
+        std::array::from_fn(|_| T::unflatten(values))
+    }
+}
+
+macro_rules! eval_params {
+    ($($name:ident: $ty:ty = $default:expr,)*) => {
+        /// Every evaluation weight, defaulting to the constants compiled into the engine
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct EvalParams {
+            $(pub $name: $ty,)*
+        }
+
+        impl Default for EvalParams {
+            fn default() -> Self {
+                EvalParams {
+                    $($name: $default,)*
+                }
+            }
+        }
+
+        impl EvalParams {
+            // Every parameter as a TOML document that `from_toml` reads back
+            pub fn to_toml(&self) -> String {
+                let mut out = String::new();
+                $(out.push_str(&format!("{} = {}\n", stringify!($name), self.$name.to_toml()));)*
+                out
+            }
+
+            // Every weight as a flat list of integers, Scores giving their mg then eg value
+            pub fn to_weights(&self) -> Vec<i32> {
+                let mut weights = Vec::new();
+                $(self.$name.flatten(&mut weights);)*
+                weights
+            }
+
+            // Inverse of `to_weights`
+            pub fn from_weights(weights: &[i32]) -> Self {
+                let mut values = weights.iter().copied();
+                EvalParams {
+                    $($name: Param::unflatten(&mut values),)*
+                }
+            }
+
+            // Parameters missing from the document keep their default value
+            pub fn from_toml(text: &str) -> Result<Self, String> {
+                let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+                let mut params = EvalParams::default();
+                for (key, value) in &table {
+                    match key.as_str() {
+                        $(stringify!($name) => {
+                            params.$name = Param::from_toml(value).map_err(|e| format!("{}: {}", key, e))?;
+                        })*
+                        _ => return Err(format!("unknown parameter {}", key)),
+                    }
+                }
+                Ok(params)
+            }
+        }
+    };
+}
+
+eval_params! {
+    // material and piece-square tables, indexed by `bitboard::piece_index`
+    piece_values: [Score; 6] = eval::PIECE_VALUES,
+    pawn_pst: [Score; 64] = eval::PAWN_PST,
+    knight_pst: [Score; 64] = eval::KNIGHT_PST,
+    bishop_pst: [Score; 64] = eval::BISHOP_PST,
+    rook_pst: [Score; 64] = eval::ROOK_PST,
+    queen_pst: [Score; 64] = eval::QUEEN_PST,
+    king_pst: [Score; 64] = eval::KING_PST,
+
+    // mobility and piece terms
+    knight_mobility: [Score; 9] = eval::KNIGHT_MOBILITY,
+    bishop_mobility: [Score; 14] = eval::BISHOP_MOBILITY,
+    rook_mobility: [Score; 15] = eval::ROOK_MOBILITY,
+    queen_mobility: [Score; 28] = eval::QUEEN_MOBILITY,
+    bishop_pair_bonus: Score = eval::BISHOP_PAIR_BONUS,
+    knight_outpost_bonus: Score = eval::KNIGHT_OUTPOST_BONUS,
+    bad_bishop_penalty: Score = eval::BAD_BISHOP_PENALTY,
+    connected_rooks_bonus: Score = eval::CONNECTED_ROOKS_BONUS,
+    trapped_rook_penalty: Score = eval::TRAPPED_ROOK_PENALTY,
+    trapped_bishop_penalty: Score = eval::TRAPPED_BISHOP_PENALTY,
+    rook_open_file_bonus: Score = eval::ROOK_OPEN_FILE_BONUS,
+    rook_semi_open_file_bonus: Score = eval::ROOK_SEMI_OPEN_FILE_BONUS,
+    rook_on_seventh_bonus: Score = eval::ROOK_ON_SEVENTH_BONUS,
+
+    // pawn structure
+    passed_pawn_bonus: [Score; 8] = pawns::PASSED_PAWN_BONUS,
+    connected_pawn_bonus: [Score; 8] = pawns::CONNECTED_PAWN_BONUS,
+    doubled_pawn_penalty: Score = pawns::DOUBLED_PAWN_PENALTY,
+    isolated_pawn_penalty: Score = pawns::ISOLATED_PAWN_PENALTY,
+    backward_pawn_penalty: Score = pawns::BACKWARD_PAWN_PENALTY,
+    passed_enemy_king_distance: i32 = pawns::PASSED_ENEMY_KING_DISTANCE,
+    passed_own_king_distance: i32 = pawns::PASSED_OWN_KING_DISTANCE,
+    unstoppable_passer_bonus: Score = pawns::UNSTOPPABLE_PASSER_BONUS,
+
+    // king safety
+    shield_close_bonus: Score = king_safety::SHIELD_CLOSE_BONUS,
+    shield_far_bonus: Score = king_safety::SHIELD_FAR_BONUS,
+    shield_missing_penalty: Score = king_safety::SHIELD_MISSING_PENALTY,
+    pawn_storm_penalty: [Score; 4] = king_safety::PAWN_STORM_PENALTY,
+    king_open_file_penalty: Score = king_safety::KING_OPEN_FILE_PENALTY,
+    king_semi_open_file_penalty: Score = king_safety::KING_SEMI_OPEN_FILE_PENALTY,
+    attacker_weights: [i32; 6] = king_safety::ATTACKER_WEIGHTS,
+    safe_check_units: [i32; 6] = king_safety::SAFE_CHECK_UNITS,
+    zone_hit_units: i32 = king_safety::ZONE_HIT_UNITS,
+    weak_zone_square_units: i32 = king_safety::WEAK_ZONE_SQUARE_UNITS,
+    no_queen_units: i32 = king_safety::NO_QUEEN_UNITS,
+    king_danger: [i32; 100] = king_safety::KING_DANGER,
+
+    // threats
+    threat_by_minor: [Score; 6] = threats::THREAT_BY_MINOR,
+    threat_by_rook: [Score; 6] = threats::THREAT_BY_ROOK,
+    threat_by_king: Score = threats::THREAT_BY_KING,
+    hanging_penalty: Score = threats::HANGING_PENALTY,
+    threat_by_safe_pawn: Score = threats::THREAT_BY_SAFE_PAWN,
+    threat_by_pawn_push: Score = threats::THREAT_BY_PAWN_PUSH,
+}
+
+impl EvalParams {
+    // Piece-square table of `piece`, indexed by `bitboard::piece_index`
+    pub fn pst(&self, piece: usize) -> &[Score; 64] {
+        match piece {
+            PAWN => &self.pawn_pst,
+            KNIGHT => &self.knight_pst,
+            BISHOP => &self.bishop_pst,
+            ROOK => &self.rook_pst,
+            QUEEN => &self.queen_pst,
+            _ => &self.king_pst,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_toml(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
@@ -0,0 +1,211 @@
+use crate::bitboard::*;
+use crate::eval::{Score, s};
+use crate::params::EvalParams;
+use crate::zobrist::Zobrist;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Pawn structure terms, rank tables indexed by the rank relative to the pawn's side
+pub const PASSED_PAWN_BONUS: [Score; 8] = [
+    s(0, 0),
+    s(5, 10),
+    s(5, 15),
+    s(10, 25),
+    s(25, 45),
+    s(45, 80),
+    s(70, 120),
+    s(0, 0),
+];
+pub const CONNECTED_PAWN_BONUS: [Score; 8] = [
+    s(0, 0),
+    s(3, 2),
+    s(5, 4),
+    s(8, 7),
+    s(14, 12),
+    s(22, 20),
+    s(35, 35),
+    s(0, 0),
+];
+pub const DOUBLED_PAWN_PENALTY: Score = s(-10, -25);
+pub const ISOLATED_PAWN_PENALTY: Score = s(-10, -15);
+pub const BACKWARD_PAWN_PENALTY: Score = s(-8, -12);
+
+// Endgame only: kings close to the square in front of a passed pawn, scaled by its rank
+pub const PASSED_ENEMY_KING_DISTANCE: i32 = 5;
+pub const PASSED_OWN_KING_DISTANCE: i32 = 2;
+// Endgame only: a passed pawn the lone enemy king cannot catch
+pub const UNSTOPPABLE_PASSER_BONUS: Score = s(0, 400);
+
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+// The keys never leave the table, fixed so that its collisions are the same on every run
+const PAWN_KEY_SEED: u64 = 0x7061_776e_6b65_7973;
+
+/// Pawn-only part of the evaluation, cached by pawn hash key
+#[derive(Clone, Copy, Default)]
+pub struct PawnEntry {
+    key: u64,
+    pub scores: [Score; 2],
+    pub passed: [u64; 2],
+    // one bit per file holding at least one pawn of that side
+    pub pawn_files: [u8; 2],
+}
+
+struct PawnTable {
+    zobrist: Zobrist,
+    entries: Vec<PawnEntry>,
+    generation: u64,
+}
+
+impl PawnTable {
+    fn new() -> Self {
+        PawnTable {
+            zobrist: Zobrist::with_seed(PAWN_KEY_SEED),
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+            generation: GENERATION.load(Ordering::Relaxed),
+        }
+    }
+
+    fn probe(&mut self, pawns: [u64; 2], params: &EvalParams) -> PawnEntry {
+        let generation = GENERATION.load(Ordering::Relaxed);
+        if self.generation != generation {
+            self.entries.fill(PawnEntry::default());
+            self.generation = generation;
+        }
+        let key = self.zobrist.hash_pawns(pawns);
+        let entry = &mut self.entries[key as usize % PAWN_TABLE_SIZE];
+        if entry.key != key {
+            *entry = evaluate_pawns(pawns, key, params);
+        }
+        *entry
+    }
+}
+
+// Every thread has its own table, bumped to empty all of them at their next probe
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static PAWN_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable::new());
+}
+
+// `pawns` holds the white then the black pawn bitboard
+pub fn probe(pawns: [u64; 2], params: &EvalParams) -> PawnEntry {
+    PAWN_TABLE.with_borrow_mut(|table| table.probe(pawns, params))
+}
+
+// Entries were computed with the parameters in use at the time, drop them when those change
+pub fn clear() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+fn evaluate_pawns(pawns: [u64; 2], key: u64, params: &EvalParams) -> PawnEntry {
+    let mut entry = PawnEntry {
+        key,
+        ..Default::default()
+    };
+
+    for color in 0..2 {
+        let own = pawns[color];
+        let enemy = pawns[1 - color];
+        let mut score = Score::ZERO;
+
+        for sq in squares(own) {
+            let file = square_file(sq);
+            entry.pawn_files[color] |= 1 << file;
+            let rank = square_rank(sq);
+            let rel_rank = relative_rank(color, rank);
+            let ahead = forward_ranks(color, rank);
+            let behind = !ahead & !rank_mask(rank);
+            let neighbours = own & adjacent_files(file);
+
+            // only the front pawn of a doubled pair can be passed
+            let doubled = own & file_mask(file) & ahead != 0;
+            if doubled {
+                score += params.doubled_pawn_penalty;
+            } else if enemy & (file_mask(file) | adjacent_files(file)) & ahead == 0 {
+                score += params.passed_pawn_bonus[rel_rank];
+                entry.passed[color] |= 1 << sq;
+            }
+
+            if neighbours == 0 {
+                score += params.isolated_pawn_penalty;
+                continue;
+            }
+
+            let phalanx = neighbours & rank_mask(rank);
+            let support_rank = if color == WHITE { rank - 1 } else { rank + 1 };
+            let supported = neighbours & rank_mask(support_rank);
+            if phalanx != 0 || supported != 0 {
+                score += params.connected_pawn_bonus[rel_rank];
+                continue;
+            }
+
+            // every neighbour is already ahead and an enemy pawn controls the stop square
+            let stop_attackers_rank = if color == WHITE {
+                rank + 2
+            } else {
+                rank.wrapping_sub(2)
+            };
+            if neighbours & behind == 0
+                && stop_attackers_rank < 8
+                && enemy & adjacent_files(file) & rank_mask(stop_attackers_rank) != 0
+            {
+                score += params.backward_pawn_penalty;
+            }
+        }
+
+        entry.scores[color] = score;
+    }
+
+    entry
+}
+
+// Passed pawn terms that depend on more than pawns: king proximity and the rule of the square.
+// `kings` are king squares and `has_pieces` tells whether each side has any non-pawn material.
+pub fn passed_pawns(
+    entry: &PawnEntry,
+    kings: [usize; 2],
+    has_pieces: [bool; 2],
+    params: &EvalParams,
+) -> [Score; 2] {
+    let mut scores = [Score::ZERO; 2];
+
+    for color in 0..2 {
+        let mut score = Score::ZERO;
+        let mut unstoppable = false;
+
+        for sq in squares(entry.passed[color]) {
+            let file = square_file(sq);
+            let rel_rank = relative_rank(color, square_rank(sq));
+            let forward = if color == WHITE { 1 } else { -1 };
+            let block = square_at(file, (square_rank(sq) as i32 + forward) as usize);
+
+            let weight = rel_rank.saturating_sub(2) as i32;
+            let proximity = params.passed_enemy_king_distance * distance(kings[1 - color], block)
+                - params.passed_own_king_distance * distance(kings[color], block);
+            score += s(0, proximity * weight);
+
+            if !has_pieces[1 - color] {
+                let promotion = square_at(file, if color == WHITE { 7 } else { 0 });
+                // a pawn on its starting rank can still push two squares
+                let mut pawn_distance = (7 - rel_rank.max(2)) as i32;
+                if file == square_file(kings[color])
+                    && forward_ranks(color, square_rank(sq)) & (1 << kings[color]) != 0
+                {
+                    pawn_distance += 1;
+                }
+                // assume the defending king moves first
+                if distance(kings[1 - color], promotion) - 1 > pawn_distance {
+                    unstoppable = true;
+                }
+            }
+        }
+
+        if unstoppable {
+            score += params.unstoppable_passer_bonus;
+        }
+
+        scores[color] = score;
+    }
+
+    scores
+}
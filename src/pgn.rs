@@ -0,0 +1,596 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::time::Duration;
+
+// Tags written first and always present, in this order, as the PGN standard requires
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// Movetext lines are wrapped below the 80 characters of the export format
+const MAX_LINE: usize = 79;
+
+/// Lexical element of PGN text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Tag(String, String),
+    MoveNumber(u32),
+    San(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+/// Streaming tokenizer over the lines of a reader
+pub struct Tokenizer<R: BufRead> {
+    reader: R,
+    line: Vec<char>,
+    pos: usize,
+    line_number: usize,
+    pending: VecDeque<Token>,
+    // set once the reader is exhausted or failed, with the error reading it if any
+    done: bool,
+    io_error: Option<String>,
+}
+
+impl<R: BufRead> Tokenizer<R> {
+    pub fn new(reader: R) -> Self {
+        Tokenizer {
+            reader,
+            line: Vec::new(),
+            pos: 0,
+            line_number: 0,
+            pending: VecDeque::new(),
+            done: false,
+            io_error: None,
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line_number, message)
+    }
+
+    // Next character, reading lines as needed. Lines starting with '%' are escaped and
+    // skipped, and the end of every line reads as a newline. Bytes that are not UTF-8, as
+    // in Latin-1 files, read as replacement characters; a read error ends the text and is
+    // kept in `io_error`.
+    fn next_char(&mut self) -> Option<char> {
+        while self.pos >= self.line.len() {
+            if self.done {
+                return None;
+            }
+            let mut bytes = Vec::new();
+            match self.reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+                    self.io_error = Some(format!("line {}: {}", self.line_number + 1, e));
+                    return None;
+                }
+            }
+            self.line_number += 1;
+            let line = String::from_utf8_lossy(&bytes);
+            if line.starts_with('%') {
+                continue;
+            }
+            self.line = line.trim_end_matches(['\n', '\r']).chars().collect();
+            self.line.push('\n');
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Some(self.line[self.pos - 1])
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.line.get(self.pos).copied()
+    }
+
+    fn skip_line(&mut self) {
+        self.pos = self.line.len();
+    }
+
+    // [Name "value"], backslashes escaping quotes and backslashes in the value
+    fn read_tag(&mut self) -> Result<Token, String> {
+        let mut name = String::new();
+        while let Some(c) = self
+            .peek_char()
+            .filter(|c| c.is_alphanumeric() || *c == '_')
+        {
+            name.push(c);
+            self.pos += 1;
+        }
+        while self.peek_char().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+        if name.is_empty() || self.peek_char() != Some('"') {
+            self.skip_line();
+            return Err(self.error("malformed tag"));
+        }
+        self.pos += 1;
+
+        let mut value = String::new();
+        loop {
+            match self.peek_char() {
+                Some('"') => break,
+                Some('\\') if matches!(self.line.get(self.pos + 1), Some('"' | '\\')) => {
+                    value.push(self.line[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some('\n') | None => {
+                    self.skip_line();
+                    return Err(self.error("unterminated tag value"));
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.pos += 1;
+
+        while self.peek_char().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+        if self.peek_char() != Some(']') {
+            self.skip_line();
+            return Err(self.error("tag not closed by ']'"));
+        }
+        self.pos += 1;
+        Ok(Token::Tag(name, value))
+    }
+
+    // {comment}, which may run over several lines
+    fn read_comment(&mut self) -> Result<Token, String> {
+        let mut comment = String::new();
+        loop {
+            match self.next_char() {
+                Some('}') => return Ok(Token::Comment(comment.trim().to_string())),
+                Some('\n') => comment.push(' '),
+                Some(c) => comment.push(c),
+                None => {
+                    return Err(self
+                        .io_error
+                        .take()
+                        .unwrap_or_else(|| self.error("unterminated comment")));
+                }
+            }
+        }
+    }
+
+    // Move numbers, results, SAN moves and their suffix annotations, which read as NAGs
+    fn read_symbol(&mut self, first: char) -> Result<(), String> {
+        let mut symbol = first.to_string();
+        while let Some(c) = self
+            .peek_char()
+            .filter(|c| c.is_alphanumeric() || "_+#=:-/.!?".contains(*c))
+        {
+            symbol.push(c);
+            self.pos += 1;
+        }
+
+        if matches!(symbol.as_str(), "1-0" | "0-1" | "1/2-1/2") {
+            self.pending.push_back(Token::Result(symbol));
+            return Ok(());
+        }
+
+        // "12.", "12..." or a number glued to its move as in "12.e4"
+        let digits = symbol.chars().take_while(char::is_ascii_digit).count();
+        let mut san = symbol.as_str();
+        if digits > 0 && symbol[digits..].starts_with('.') {
+            let number = symbol[..digits]
+                .parse()
+                .map_err(|_| self.error("move number out of range"))?;
+            self.pending.push_back(Token::MoveNumber(number));
+            san = symbol[digits..].trim_start_matches('.');
+            if san.is_empty() {
+                return Ok(());
+            }
+        } else if digits == symbol.len() {
+            // a move number without its period
+            let number = symbol
+                .parse()
+                .map_err(|_| self.error("move number out of range"))?;
+            self.pending.push_back(Token::MoveNumber(number));
+            return Ok(());
+        }
+
+        let end = san.trim_end_matches(['!', '?']).len();
+        let (mv, suffix) = san.split_at(end);
+        // castling written with zeros
+        let mv = if mv.starts_with("0-0") {
+            mv.replace('0', "O")
+        } else {
+            mv.to_string()
+        };
+        if mv.is_empty() || !mv.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err(self.error(&format!("unexpected symbol {}", symbol)));
+        }
+        self.pending.push_back(Token::San(mv));
+        if !suffix.is_empty() {
+            let nag = ["!", "?", "!!", "??", "!?", "?!"]
+                .iter()
+                .position(|s| *s == suffix)
+                .ok_or(self.error(&format!("unknown annotation {}", suffix)))?;
+            self.pending.push_back(Token::Nag(nag as u8 + 1));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for Tokenizer<R> {
+    type Item = Result<Token, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.pending.pop_front() {
+                return Some(Ok(token));
+            }
+            let Some(c) = self.next_char() else {
+                return self.io_error.take().map(Err);
+            };
+            let token = match c {
+                _ if c.is_whitespace() => continue,
+                '[' => self.read_tag(),
+                '{' => self.read_comment(),
+                ';' => {
+                    let comment: String = self.line[self.pos..].iter().collect();
+                    self.skip_line();
+                    Ok(Token::Comment(comment.trim().to_string()))
+                }
+                '(' => Ok(Token::VariationStart),
+                ')' => Ok(Token::VariationEnd),
+                '*' => Ok(Token::Result(String::from("*"))),
+                '$' => {
+                    let mut digits = String::new();
+                    while let Some(c) = self.peek_char().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        self.pos += 1;
+                    }
+                    digits
+                        .parse()
+                        .map(Token::Nag)
+                        .map_err(|_| self.error("malformed NAG"))
+                }
+                _ if c.is_alphanumeric() => match self.read_symbol(c) {
+                    Ok(()) => continue,
+                    Err(e) => Err(e),
+                },
+                _ => Err(self.error(&format!("unexpected character '{}'", c))),
+            };
+            return Some(token);
+        }
+    }
+}
+
+/// A move of the movetext with its annotations, and the lines played instead of it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(san: &str) -> Self {
+        PgnMove {
+            san: san.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Records a clock command in the comment of the move, replacing any previous one of
+    // the same kind: "clk" for the clock time left after the move, "emt" for the time it
+    // took. Times are written as h:mm:ss with tenths of a second when there are any.
+    pub fn set_clock(&mut self, command: &str, time: Duration) {
+        let tenths = time.as_millis() / 100;
+        let secs = tenths / 10;
+        let mut clock = format!(
+            "[%{} {}:{:02}:{:02}",
+            command,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        if !tenths.is_multiple_of(10) {
+            clock += &format!(".{}", tenths % 10);
+        }
+        clock.push(']');
+
+        let prefix = format!("[%{} ", command);
+        let mut comment = self.comment.take().unwrap_or_default();
+        if let Some(start) = comment.find(&prefix)
+            && let Some(end) = comment[start..].find(']')
+        {
+            comment.replace_range(start..start + end + 1, &clock);
+        } else if comment.is_empty() {
+            comment = clock;
+        } else {
+            comment = format!("{} {}", clock, comment);
+        }
+        self.comment = Some(comment);
+    }
+}
+
+/// A game of a PGN file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    // comment ahead of the first move
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    // "1-0", "0-1", "1/2-1/2" or "*"
+    pub result: String,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game {
+            tags: Vec::new(),
+            comment: None,
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+}
+
+impl Game {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Starting position when the game does not start from the initial one
+    pub fn fen(&self) -> Option<&str> {
+        self.tag("FEN")
+    }
+
+    // Result from White's point of view, None for unfinished games
+    pub fn score(&self) -> Option<f64> {
+        match self.result.as_str() {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None,
+        }
+    }
+
+    // Moves of the main line
+    pub fn sans(&self) -> impl Iterator<Item = &str> {
+        self.moves.iter().map(|mv| mv.san.as_str())
+    }
+
+    // Export format: the seven tag roster first, then the other tags and the movetext
+    // wrapped below 80 columns
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for name in SEVEN_TAG_ROSTER {
+            // unknown values, written with question marks as the standard does
+            let value = match name {
+                "Result" => self.result.as_str(),
+                "Date" => self.tag(name).unwrap_or("????.??.??"),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            pgn += &format!("[{} \"{}\"]\n", name, escape(value));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                pgn += &format!("[{} \"{}\"]\n", name, escape(value));
+            }
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+        }
+        write_line(&self.moves, first_ply(self.fen()), &mut tokens);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            let glued = line.ends_with('(') || token == ")";
+            if !line.is_empty() && !glued && line.len() + 1 + token.len() > MAX_LINE {
+                pgn += &line;
+                pgn.push('\n');
+                line.clear();
+            } else if !line.is_empty() && !glued {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        pgn += &line;
+        pgn += "\n\n";
+        pgn
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Ply of the first move, 0 for White's first move, from the move counters of the FEN
+fn first_ply(fen: Option<&str>) -> usize {
+    let Some(fen) = fen else {
+        return 0;
+    };
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let fullmove: usize = fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+    2 * fullmove.saturating_sub(1) + (fields.get(1) == Some(&"b")) as usize
+}
+
+// Movetext tokens of a line starting at `ply`, Black's moves numbered as "12..." when
+// a comment or a variation comes before them
+fn write_line(moves: &[PgnMove], mut ply: usize, tokens: &mut Vec<String>) {
+    let mut numbered = false;
+    for mv in moves {
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if !numbered {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        tokens.push(mv.san.clone());
+        tokens.extend(mv.nags.iter().map(|nag| format!("${}", nag)));
+        numbered = true;
+
+        if let Some(comment) = &mv.comment {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+            numbered = false;
+        }
+        for variation in &mv.variations {
+            tokens.push(String::from("("));
+            write_line(variation, ply, tokens);
+            tokens.push(String::from(")"));
+            numbered = false;
+        }
+        ply += 1;
+    }
+}
+
+/// Reads the games of PGN text one at a time. A malformed game is reported as an error
+/// and skipped up to its result or the tags of the next game, reading then goes on.
+pub struct Reader<R: BufRead> {
+    tokens: Tokenizer<R>,
+    peeked: Option<Token>,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader {
+            tokens: Tokenizer::new(reader),
+            peeked: None,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Result<Token, String>> {
+        self.peeked.take().map(Ok).or_else(|| self.tokens.next())
+    }
+
+    // Skips the rest of a malformed game
+    fn recover(&mut self) {
+        while let Some(token) = self.next_token() {
+            match token {
+                Ok(Token::Result(_)) => return,
+                Ok(tag @ Token::Tag(..)) => {
+                    self.peeked = Some(tag);
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_game(&mut self) -> Option<Result<Game, String>> {
+        let mut game = Game::default();
+        // lines being read, the main line first and the innermost variation last
+        let mut lines: Vec<Vec<PgnMove>> = vec![Vec::new()];
+        let mut started = false;
+        let mut result = None;
+
+        while let Some(token) = self.next_token() {
+            let token = match token {
+                Ok(token) => token,
+                Err(e) => {
+                    self.recover();
+                    return Some(Err(e));
+                }
+            };
+            match token {
+                Token::Tag(..) if !lines[0].is_empty() => {
+                    // tags of the next game, this one ended without a result
+                    self.peeked = Some(token);
+                    break;
+                }
+                Token::Tag(name, value) => game.tags.push((name, value)),
+                Token::MoveNumber(_) => {}
+                Token::San(san) => lines.last_mut().unwrap().push(PgnMove::new(&san)),
+                Token::Nag(nag) => match lines.last_mut().unwrap().last_mut() {
+                    Some(mv) => mv.nags.push(nag),
+                    None => return Some(Err(self.fail("NAG before any move"))),
+                },
+                Token::Comment(comment) => {
+                    let depth = lines.len() - 1;
+                    let target = if let Some(mv) = lines[depth].last_mut() {
+                        &mut mv.comment
+                    } else if depth == 0 {
+                        &mut game.comment
+                    } else {
+                        // a comment opening a variation goes with the move it replaces
+                        &mut lines[depth - 1].last_mut().unwrap().comment
+                    };
+                    *target = Some(match target.take() {
+                        Some(old) => format!("{} {}", old, comment),
+                        None => comment,
+                    });
+                }
+                Token::VariationStart => {
+                    if lines.last().unwrap().is_empty() {
+                        return Some(Err(self.fail("variation before any move")));
+                    }
+                    lines.push(Vec::new());
+                }
+                Token::VariationEnd => {
+                    if lines.len() == 1 {
+                        return Some(Err(self.fail("')' without a variation")));
+                    }
+                    let variation = lines.pop().unwrap();
+                    let line = lines.last_mut().unwrap();
+                    line.last_mut().unwrap().variations.push(variation);
+                }
+                Token::Result(token) => {
+                    if lines.len() > 1 {
+                        return Some(Err(self.fail_here("unclosed variation")));
+                    }
+                    result = Some(token);
+                    started = true;
+                    break;
+                }
+            }
+            started = true;
+        }
+
+        if !started {
+            return None;
+        }
+        if lines.len() > 1 {
+            return Some(Err(self.fail_here("unclosed variation")));
+        }
+        game.moves = lines.pop().unwrap();
+        game.result = result
+            .or_else(|| game.tag("Result").map(str::to_string))
+            .unwrap_or_else(|| String::from("*"));
+        Some(Ok(game))
+    }
+
+    fn fail(&mut self, message: &str) -> String {
+        let error = self.tokens.error(message);
+        self.recover();
+        error
+    }
+
+    // Error found at the end of a game, nothing left to skip
+    fn fail_here(&self, message: &str) -> String {
+        self.tokens.error(message)
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Game, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_game()
+    }
+}
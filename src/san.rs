@@ -0,0 +1,210 @@
+use crate::moves::generate_legal_moves;
+use crate::uci::{square_from_uci, square_to_uci};
+use crabchess::prelude::*;
+
+// Origin, destination and moving piece of any move but castling
+fn squares(mv: &Move) -> Option<(Square, Square, Type)> {
+    match *mv {
+        Move::Standard {
+            initial_square,
+            final_square,
+            piece_type,
+            ..
+        } => Some((initial_square, final_square, piece_type)),
+        Move::EnPassant {
+            initial_square,
+            final_square,
+            ..
+        }
+        | Move::PawnPromotion {
+            initial_square,
+            final_square,
+            ..
+        } => Some((initial_square, final_square, Type::Pawn)),
+        Move::Castle { .. } => None,
+    }
+}
+
+fn piece_letter(piece: Type) -> &'static str {
+    match piece {
+        Type::Pawn => "",
+        Type::Knight => "N",
+        Type::Bishop => "B",
+        Type::Rook => "R",
+        Type::Queen => "Q",
+        Type::King => "K",
+    }
+}
+
+fn promotion_letter(promotion: PromotedType) -> char {
+    match promotion {
+        PromotedType::Knight => 'N',
+        PromotedType::Bishop => 'B',
+        PromotedType::Rook => 'R',
+        PromotedType::Queen => 'Q',
+    }
+}
+
+// Standard algebraic notation of a legal move, with just enough of its origin to tell it
+// apart from the other moves of the same piece type, and a check or mate suffix
+pub fn san(pos: &ChessPosition, turn: Color, mv: &Move) -> String {
+    let mut san = match (mv, squares(mv)) {
+        (Move::Castle { side, .. }, _) => match side {
+            Side::Kingside => String::from("O-O"),
+            Side::Queenside => String::from("O-O-O"),
+        },
+        (_, Some((from, to, piece))) => {
+            let capture = pos.get(to).is_some() || matches!(mv, Move::EnPassant { .. });
+            let from_name = square_to_uci(from);
+            let mut san = piece_letter(piece).to_string();
+
+            if piece == Type::Pawn {
+                if capture {
+                    san.push_str(&from_name[..1]);
+                }
+            } else {
+                let others: Vec<Square> = generate_legal_moves(pos, turn, None, &[None; 2])
+                    .iter()
+                    .filter_map(squares)
+                    .filter(|&(other, other_to, other_piece)| {
+                        other_piece == piece && other_to == to && other != from
+                    })
+                    .map(|(other, _, _)| other)
+                    .collect();
+                if !others.is_empty() {
+                    if others.iter().all(|other| other.file() != from.file()) {
+                        san.push_str(&from_name[..1]);
+                    } else if others.iter().all(|other| other.rank() != from.rank()) {
+                        san.push_str(&from_name[1..]);
+                    } else {
+                        san.push_str(&from_name);
+                    }
+                }
+            }
+
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&square_to_uci(to));
+            if let Move::PawnPromotion { new_type, .. } = mv {
+                san.push('=');
+                san.push(promotion_letter(*new_type));
+            }
+            san
+        }
+        _ => unreachable!(),
+    };
+
+    let mut next = pos.clone();
+    if next.apply_move(*mv).is_ok() && next.is_in_check(turn.other()) {
+        let mated = generate_legal_moves(&next, turn.other(), None, &[None; 2]).is_empty();
+        san.push(if mated { '#' } else { '+' });
+    }
+    san
+}
+
+// Legal move written in SAN. Check marks and annotations are ignored, and so are capture
+// marks and extra origin squares, castling may be written with zeros.
+pub fn parse_san(pos: &ChessPosition, turn: Color, text: &str) -> Result<Move, String> {
+    let mut san = text.trim_end_matches(['+', '#', '!', '?']).to_string();
+    if san.starts_with("0-0") {
+        san = san.replace('0', "O");
+    }
+    let legal = generate_legal_moves(pos, turn, None, &[None; 2]);
+
+    let candidates: Vec<Move> = if san == "O-O" || san == "O-O-O" {
+        let side = if san == "O-O" {
+            Side::Kingside
+        } else {
+            Side::Queenside
+        };
+        legal
+            .into_iter()
+            .filter(|mv| matches!(mv, Move::Castle { side: s, .. } if *s == side))
+            .collect()
+    } else {
+        let (piece, rest) = match san.chars().next() {
+            Some('N') => (Type::Knight, &san[1..]),
+            Some('B') => (Type::Bishop, &san[1..]),
+            Some('R') => (Type::Rook, &san[1..]),
+            Some('Q') => (Type::Queen, &san[1..]),
+            Some('K') => (Type::King, &san[1..]),
+            _ => (Type::Pawn, san.as_str()),
+        };
+        // promotions as "e8=Q" or "e8Q"
+        let (rest, promotion) = match rest.char_indices().last() {
+            Some((i, c)) if piece == Type::Pawn && "NBRQ".contains(c) => {
+                let promotion = match c {
+                    'N' => PromotedType::Knight,
+                    'B' => PromotedType::Bishop,
+                    'R' => PromotedType::Rook,
+                    _ => PromotedType::Queen,
+                };
+                (rest[..i].trim_end_matches('='), Some(promotion))
+            }
+            _ => (rest, None),
+        };
+
+        let squares_text: String = rest.chars().filter(|c| !"x-:".contains(*c)).collect();
+        let valid = |c: char| ('a'..='h').contains(&c) || ('1'..='8').contains(&c);
+        if squares_text.len() < 2 || !squares_text.chars().all(valid) {
+            return Err(format!("unreadable move {}", text));
+        }
+        let (hints, to) = squares_text.split_at(squares_text.len() - 2);
+        let to = square_from_uci(to).ok_or(format!("unreadable move {}", text))?;
+
+        legal
+            .into_iter()
+            .filter(|mv| {
+                let Some((from, mv_to, mv_piece)) = squares(mv) else {
+                    return false;
+                };
+                let from_name = square_to_uci(from);
+                let mv_promotion = match mv {
+                    Move::PawnPromotion { new_type, .. } => Some(*new_type),
+                    _ => None,
+                };
+                mv_piece == piece
+                    && mv_to == to
+                    && mv_promotion == promotion
+                    && hints.chars().all(|hint| from_name.contains(hint))
+            })
+            .collect()
+    };
+
+    match candidates.len() {
+        0 => Err(format!("illegal move {}", text)),
+        1 => Ok(candidates[0]),
+        _ => Err(format!("ambiguous move {}", text)),
+    }
+}
+
+// Moves of a line in SAN with their move numbers, as in "12... Nf6 13. Bg5 Be7"
+pub fn line(pos: &ChessPosition, turn: Color, moves: &[Move]) -> String {
+    let fen = pos.fen();
+    let mut number: usize = fen
+        .split_whitespace()
+        .nth(5)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1);
+    let mut pos = pos.clone();
+    let mut turn = turn;
+    let mut tokens = Vec::new();
+
+    for (i, mv) in moves.iter().enumerate() {
+        if turn == Color::White {
+            tokens.push(format!("{}.", number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san(&pos, turn, mv));
+        if pos.apply_move(*mv).is_err() {
+            break;
+        }
+        if turn == Color::Black {
+            number += 1;
+        }
+        turn = turn.other();
+    }
+    tokens.join(" ")
+}
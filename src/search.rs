@@ -1,11 +1,15 @@
-use crate::eval::evaluate;
+use crate::bitboard::{Bitboards, color_index};
+use crate::endgame;
+use crate::eval::{Accumulator, MAX_PHASE, MoveDelta, evaluate, evaluate_with};
 use crate::moves::*;
+use crate::nnue::{Network, NnueAccumulator};
+use crate::params::EvalParams;
 use crate::zobrist::Zobrist;
 use crabchess::prelude::*;
 use std::collections::HashMap;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
     Arc,
+    atomic::{AtomicBool, Ordering},
 };
 
 enum Bound {
@@ -23,330 +27,652 @@ pub struct TTEntry {
 
 pub type TranspositionTable = HashMap<u64, TTEntry>;
 
-pub const MAX_DEPTH: usize = 20;
+// Hard limit on the length of a search line, extensions included
+pub const MAX_PLY: usize = 128;
 pub const WHITE_MATE: i32 = 1_000_000;
 pub const BLACK_MATE: i32 = -1_000_000;
+// Scores beyond this bound are forced mates, the remainder being the distance in plies
+pub const MATE_BOUND: i32 = WHITE_MATE - MAX_PLY as i32;
+
+// Mate scores are relative to the root in the search but relative to the node in the TT,
+// so that a mate found through one path keeps its distance when reached through another
+fn value_to_tt(value: i32, ply: usize) -> i32 {
+    if value >= MATE_BOUND {
+        value + ply as i32
+    } else if value <= -MATE_BOUND {
+        value - ply as i32
+    } else {
+        value
+    }
+}
 
-pub fn aspiration_search(
-    pos: &ChessPosition,
-    turn: Color,
-    guess: i32,
-    depth: usize,
-    mut window: i32,
-    tt: &mut TranspositionTable,
-    zob: &mut Zobrist,
-    stop_flag: &Arc<AtomicBool>,
-) -> (Move, i32) {
-    let mut alpha = (guess - window).max(BLACK_MATE);
-    let mut beta = (guess + window).min(WHITE_MATE);
-    let mut best_move = None;
-    let mut score = guess;
-    let mut killer_moves: [[Option<Move>; 2]; MAX_DEPTH] = [[None; 2]; MAX_DEPTH];
-
-    loop {
-        if stop_flag.load(Ordering::Relaxed) {
-            break; // exit immediately if time is up
-        }
+fn value_from_tt(value: i32, ply: usize) -> i32 {
+    if value >= MATE_BOUND {
+        value - ply as i32
+    } else if value <= -MATE_BOUND {
+        value + ply as i32
+    } else {
+        value
+    }
+}
 
-        let (mv, val) = minimax(
-            pos,
-            turn,
-            turn,
-            depth,
-            depth,
-            alpha,
-            beta,
-            turn == Color::White,
-            tt,
-            zob,
-            &mut killer_moves,
-            stop_flag,
-        );
-        best_move = mv;
-        score = val;
+// Number of moves until mate from the side to move's point of view, negative when getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE_BOUND {
+        Some((WHITE_MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        Some(-(WHITE_MATE + score) / 2)
+    } else {
+        None
+    }
+}
 
-        if turn == Color::White && score > WHITE_MATE - MAX_DEPTH as i32 {
-            return (best_move.unwrap(), score); // forced mate found, stop search
+/// How much the engine dislikes a draw, in centipawns from its own point of view.
+#[derive(Clone, Copy)]
+pub struct Contempt {
+    pub value: i32,
+    pub scale_by_phase: bool,
+}
+
+impl Default for Contempt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Contempt {
+    pub fn new() -> Self {
+        Contempt {
+            value: 0,
+            scale_by_phase: true,
         }
-        if turn == Color::Black && score < BLACK_MATE + MAX_DEPTH as i32 {
-            return (best_move.unwrap(), score); // forced mate found, stop search
+    }
+
+    // Draw score from White's point of view, `engine_side` being the side to move at the root
+    pub fn draw_score(&self, phase: i32, engine_side: Color) -> i32 {
+        let mut value = self.value;
+        if self.scale_by_phase {
+            value = value * phase.min(MAX_PHASE) / MAX_PHASE;
         }
 
-        if score <= alpha {
-            // fail low → widen window downward
-            beta = alpha;
-            alpha = (score - window).max(BLACK_MATE);
-        } else if score >= beta {
-            // fail high → widen window upward
-            beta = (score + window).min(WHITE_MATE);
+        if engine_side == Color::White {
+            -value
         } else {
-            break; // score is within [alpha, beta]
+            value
         }
-        window += window / 2;
     }
+}
 
-    (best_move.unwrap(), score)
+/// Positions reached in the real game followed by the current search path.
+/// Each entry keeps the Zobrist hash and the halfmove clock at that point.
+#[derive(Clone)]
+pub struct History {
+    entries: Vec<(u64, u16)>,
+    root: usize,
 }
 
-pub fn mtdf(
-    position: &ChessPosition,
-    turn: Color,
-    first_guess: i32,
-    depth: usize,
-    tt: &mut TranspositionTable,
-    zob: &mut Zobrist,
-    stop_flag: &Arc<AtomicBool>,
-) -> (Move, i32) {
-    let mut guess = first_guess;
-    let mut upper_bound = WHITE_MATE;
-    let mut lower_bound = BLACK_MATE;
-    let mut killer_moves: [[Option<Move>; 2]; MAX_DEPTH] = [[None; 2]; MAX_DEPTH];
-
-    while lower_bound < upper_bound {
-        if stop_flag.load(Ordering::Relaxed) {
-            break; // exit immediately if time is up
+impl History {
+    // `halfmove_clock` carries over the plies already played towards the fifty-move rule
+    pub fn new(hash: u64, halfmove_clock: u16) -> Self {
+        History {
+            entries: vec![(hash, halfmove_clock)],
+            root: 0,
         }
-        let beta = guess.max(lower_bound + 1);
-        let (_, eval) = minimax(
-            position,
-            turn,
-            turn,
-            depth,
-            depth,
-            beta - 1,
-            beta,
-            turn == Color::White,
+    }
+
+    // A capture or a pawn move resets the fifty-move counter
+    pub fn push(&mut self, hash: u64, irreversible: bool) {
+        let halfmove_clock = if irreversible {
+            0
+        } else {
+            self.halfmove_clock() + 1
+        };
+        self.entries.push((hash, halfmove_clock));
+    }
+
+    pub fn pop(&mut self) {
+        self.entries.pop();
+    }
+
+    // Everything pushed after this call belongs to the search path
+    pub fn set_root(&mut self) {
+        self.root = self.entries.len() - 1;
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.entries.last().unwrap().0
+    }
+
+    pub fn halfmove_clock(&self) -> u16 {
+        self.entries.last().unwrap().1
+    }
+
+    // A single repetition inside the search path is enough to call it a draw,
+    // positions from the real game need to have occurred twice already
+    pub fn is_repetition(&self) -> bool {
+        let last = self.entries.len() - 1;
+        let (hash, halfmove_clock) = self.entries[last];
+        let reach = (halfmove_clock as usize).min(last);
+        if reach < 4 {
+            return false;
+        }
+
+        let mut seen_in_game = 0;
+        for i in (last - reach..=last - 4).rev().step_by(2) {
+            if self.entries[i].0 == hash {
+                if i >= self.root {
+                    return true;
+                }
+                seen_in_game += 1;
+                if seen_in_game >= 2 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// State of a single search: the shared tables plus the stacks indexed by ply
+pub struct Searcher<'a> {
+    tt: &'a mut TranspositionTable,
+    zob: &'a Zobrist,
+    params: &'a EvalParams,
+    network: Option<&'a Network>,
+    history: &'a mut History,
+    contempt: Contempt,
+    stop_flag: &'a Arc<AtomicBool>,
+    killer_moves: [[Option<Move>; 2]; MAX_PLY],
+    boards: [Bitboards; MAX_PLY],
+    accumulators: [Accumulator; MAX_PLY],
+    // only filled when searching with a network
+    nnue: Vec<NnueAccumulator>,
+    root_side: Color,
+    root_depth: usize,
+    pub seldepth: usize,
+    pub nodes: u64,
+    // the search stops itself once this many nodes were visited
+    pub node_limit: Option<u64>,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(
+        tt: &'a mut TranspositionTable,
+        zob: &'a Zobrist,
+        params: &'a EvalParams,
+        network: Option<&'a Network>,
+        history: &'a mut History,
+        contempt: Contempt,
+        stop_flag: &'a Arc<AtomicBool>,
+    ) -> Self {
+        history.set_root();
+        Searcher {
             tt,
             zob,
-            &mut killer_moves,
+            params,
+            network,
+            history,
+            contempt,
             stop_flag,
-        );
-        guess = eval;
-        if guess < beta {
-            // fail low
-            upper_bound = guess;
-        } else {
-            // fail high
-            lower_bound = guess;
+            killer_moves: [[None; 2]; MAX_PLY],
+            boards: [Bitboards::default(); MAX_PLY],
+            accumulators: [Accumulator::default(); MAX_PLY],
+            nnue: network.map_or(Vec::new(), |network| {
+                vec![NnueAccumulator::empty(network.hidden_size()); MAX_PLY]
+            }),
+            root_side: Color::White,
+            root_depth: 0,
+            seldepth: 0,
+            nodes: 0,
+            node_limit: None,
         }
     }
 
-    // after convergence, lookup root move from TT
-    let hash = zob.hash_position(position, turn);
-    let best_move = tt.get(&hash).unwrap().best_move.unwrap();
+    pub fn aspiration_search(
+        &mut self,
+        pos: &ChessPosition,
+        turn: Color,
+        guess: i32,
+        depth: usize,
+        mut window: i32,
+    ) -> Option<(Move, i32)> {
+        let mut alpha = (guess - window).max(BLACK_MATE);
+        let mut beta = (guess + window).min(WHITE_MATE);
+        let mut best_move = None;
+        let mut score = guess;
+        self.root_side = turn;
+        self.root_depth = depth;
+        self.seldepth = 0;
+        self.set_root_accumulators(pos);
+
+        loop {
+            if self.stop_flag.load(Ordering::Relaxed) {
+                break; // exit immediately if time is up
+            }
 
-    (best_move, guess)
-}
+            let (mv, val) = self.minimax(pos, turn, depth, 0, alpha, beta, turn == Color::White);
+            best_move = mv;
+            score = val;
 
-fn minimax(
-    position: &ChessPosition,
-    turn: Color,
-    side_to_move: Color,
-    depth: usize,
-    original_depth: usize,
-    mut alpha: i32,
-    mut beta: i32,
-    maximizing: bool,
-    tt: &mut TranspositionTable,
-    zob: &mut Zobrist,
-    killer_moves: &mut [[Option<Move>; 2]; MAX_DEPTH],
-    stop_flag: &Arc<AtomicBool>,
-) -> (Option<Move>, i32) {
-    if position.threefold_repetition() {
-        return (None, 0);
-    }
+            if turn == Color::White && score >= MATE_BOUND {
+                return best_move.map(|mv| (mv, score)); // forced mate found, stop search
+            }
+            if turn == Color::Black && score <= -MATE_BOUND {
+                return best_move.map(|mv| (mv, score)); // forced mate found, stop search
+            }
 
-    let hash = zob.hash_position(position, turn);
-    let mut tt_move = None;
-    if let Some(entry) = tt.get(&hash) {
-        tt_move = entry.best_move;
-        if entry.depth >= depth {
-            match entry.bound {
-                Bound::Exact => return (entry.best_move, entry.value),
-                Bound::Lower if entry.value >= beta => return (entry.best_move, entry.value),
-                Bound::Upper if entry.value <= alpha => return (entry.best_move, entry.value),
-                _ => {}
+            if score <= alpha {
+                // fail low → widen window downward
+                beta = alpha;
+                alpha = (score - window).max(BLACK_MATE);
+            } else if score >= beta {
+                // fail high → widen window upward
+                beta = (score + window).min(WHITE_MATE);
+            } else {
+                break; // score is within [alpha, beta]
             }
+            window += window / 2;
         }
+
+        // no move when the search was stopped before the first one was finished
+        best_move.map(|mv| (mv, score))
     }
 
-    match position.status() {
-        PositionStatus::Stalemate
-        | PositionStatus::InsufficientMaterial
-        | PositionStatus::FiftyMoveRule => return (None, 0),
-        PositionStatus::Checkmate(_) => {
-            return if maximizing {
-                (None, BLACK_MATE)
-            } else {
-                (None, WHITE_MATE)
+    // Principal variation: the best root move, then the TT moves that follow it, stopping
+    // at the first missing or illegal move, a repetition or `depth` moves
+    pub fn pv(&self, pos: &ChessPosition, turn: Color, best: Move, depth: usize) -> Vec<Move> {
+        let mut pv = vec![best];
+        let mut pos = pos.clone();
+        let mut turn = turn;
+        let mut seen = vec![self.zob.hash_position(&pos, turn)];
+
+        while pv.len() < depth {
+            if pos.apply_move(*pv.last().unwrap()).is_err() {
+                break;
+            }
+            turn = turn.other();
+            let hash = self.zob.hash_position(&pos, turn);
+            if seen.contains(&hash) {
+                break;
+            }
+            seen.push(hash);
+
+            let Some(mv) = self.tt.get(&hash).and_then(|entry| entry.best_move) else {
+                break;
             };
+            if !generate_legal_moves(&pos, turn, None, &[None; 2]).contains(&mv) {
+                break;
+            }
+            pv.push(mv);
         }
-        _ => {}
+        pv
     }
 
-    if depth == 0 {
-        // Experimental
-        // tempo bonus to limit the even-odd instability
-        /*let tempo_bonus = if turn == side_to_move && turn == Color::White {
-            20 * (original_depth as i32 - 2)
-        } else if turn == side_to_move && turn == Color::Black {
-            -20 * original_depth as i32
-        } else {
-            0
-        };
-        let eval = evaluate(position) + tempo_bonus;*/
-        /*let mut eval = quiesce(position, turn, alpha, beta, tt, zob);
-        if turn == Color::Black {
-            eval = -eval;
-        }*/
-        let eval = evaluate(position);
-        tt.insert(
-            hash,
-            TTEntry {
-                best_move: None,
-                depth: 0,
-                value: eval,
-                bound: Bound::Exact,
-            },
-        );
-        return (None, eval);
+    pub fn mtdf(
+        &mut self,
+        position: &ChessPosition,
+        turn: Color,
+        first_guess: i32,
+        depth: usize,
+    ) -> (Move, i32) {
+        let mut guess = first_guess;
+        let mut upper_bound = WHITE_MATE;
+        let mut lower_bound = BLACK_MATE;
+        self.root_side = turn;
+        self.root_depth = depth;
+        self.seldepth = 0;
+        self.set_root_accumulators(position);
+
+        while lower_bound < upper_bound {
+            if self.stop_flag.load(Ordering::Relaxed) {
+                break; // exit immediately if time is up
+            }
+            let beta = guess.max(lower_bound + 1);
+            let (_, eval) = self.minimax(
+                position,
+                turn,
+                depth,
+                0,
+                beta - 1,
+                beta,
+                turn == Color::White,
+            );
+            guess = eval;
+            if guess < beta {
+                // fail low
+                upper_bound = guess;
+            } else {
+                // fail high
+                lower_bound = guess;
+            }
+        }
+
+        // after convergence, lookup root move from TT
+        let hash = self.zob.hash_position(position, turn);
+        let best_move = self.tt.get(&hash).unwrap().best_move.unwrap();
+
+        (best_move, guess)
     }
 
-    let moves = generate_legal_moves(&position, turn, tt_move, &killer_moves[depth as usize]);
+    // Bitboards and accumulators of the root position, every other ply is updated from its
+    // parent
+    fn set_root_accumulators(&mut self, position: &ChessPosition) {
+        let board = Bitboards::new(position);
+        self.boards[0] = board;
+        self.accumulators[0] = Accumulator::from_board(&board, self.params);
+        if let Some(network) = self.network {
+            self.nnue[0] = network.refresh(&board);
+        }
+    }
 
-    if maximizing {
-        let mut max_eval = BLACK_MATE;
-        let mut best_move = None;
-        let alpha_orig = alpha;
+    #[allow(clippy::too_many_arguments)]
+    fn minimax(
+        &mut self,
+        position: &ChessPosition,
+        turn: Color,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+        maximizing: bool,
+    ) -> (Option<Move>, i32) {
+        self.seldepth = self.seldepth.max(ply);
+        self.nodes += 1;
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.stop_flag.store(true, Ordering::Relaxed);
+        }
 
-        for mv in moves {
-            if stop_flag.load(Ordering::Relaxed) {
-                return (best_move, max_eval); // exit immediately if time is up
+        // the root always needs a move, draws are only scored below it
+        if ply > 0 {
+            if self.history.is_repetition() {
+                return (
+                    None,
+                    self.contempt
+                        .draw_score(self.accumulators[ply].phase, self.root_side),
+                );
             }
-            let mut child = position.clone();
-            if let Err(_) = child.apply_move(mv) {
-                continue;
+            if self.history.halfmove_clock() >= 100 && !position.checkmated(turn) {
+                return (
+                    None,
+                    self.contempt
+                        .draw_score(self.accumulators[ply].phase, self.root_side),
+                );
             }
 
-            let (_, mut eval) = minimax(
-                &child,
-                turn.other(),
-                side_to_move,
-                depth - 1,
-                original_depth,
-                alpha,
-                beta,
-                false,
-                tt,
-                zob,
-                killer_moves,
-                stop_flag,
-            );
+            // mate distance pruning: nothing below can beat a mate already closer to the root
+            alpha = alpha.max(BLACK_MATE + ply as i32);
+            beta = beta.min(WHITE_MATE - ply as i32);
+            if alpha >= beta {
+                return (None, alpha);
+            }
+        }
 
-            if eval > WHITE_MATE - MAX_DEPTH as i32 {
-                eval -= 1; // handle mate in n moves
+        let hash = self.history.hash();
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.get(&hash) {
+            tt_move = entry.best_move;
+            if entry.depth >= depth {
+                let value = value_from_tt(entry.value, ply);
+                match entry.bound {
+                    Bound::Exact => return (entry.best_move, value),
+                    Bound::Lower if value >= beta => return (entry.best_move, value),
+                    Bound::Upper if value <= alpha => return (entry.best_move, value),
+                    _ => {}
+                }
             }
+        }
 
-            if eval > max_eval {
-                max_eval = eval;
-                best_move = Some(mv);
+        match position.status() {
+            PositionStatus::Stalemate
+            | PositionStatus::InsufficientMaterial
+            | PositionStatus::FiftyMoveRule => {
+                return (
+                    None,
+                    self.contempt
+                        .draw_score(self.accumulators[ply].phase, self.root_side),
+                );
             }
+            PositionStatus::Checkmate(_) => {
+                return if maximizing {
+                    (None, BLACK_MATE + ply as i32)
+                } else {
+                    (None, WHITE_MATE - ply as i32)
+                };
+            }
+            _ => {}
+        }
 
-            alpha = alpha.max(eval);
-            if max_eval >= beta {
-                killer_moves[depth as usize].rotate_right(1);
-                killer_moves[depth as usize][0] = Some(mv);
-                break; // beta cutoff
+        // king and pawn against king is settled by the bitbase, nothing to search
+        if ply > 0
+            && self.accumulators[ply].phase == 0
+            && let Some(eval) =
+                endgame::probe_kpk(&self.boards[ply], color_index(turn), self.params)
+        {
+            if eval == 0 {
+                return (
+                    None,
+                    self.contempt
+                        .draw_score(self.accumulators[ply].phase, self.root_side),
+                );
             }
+            return (None, eval);
         }
 
-        // Store in TT
-        let bound = if max_eval <= alpha_orig {
-            Bound::Upper
-        } else if max_eval >= beta {
-            Bound::Lower
-        } else {
-            Bound::Exact
-        };
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            // Experimental
+            // tempo bonus to limit the even-odd instability
+            /*let tempo_bonus = if turn == side_to_move && turn == Color::White {
+                20 * (original_depth as i32 - 2)
+            } else if turn == side_to_move && turn == Color::Black {
+                -20 * original_depth as i32
+            } else {
+                0
+            };
+            let eval = evaluate(position) + tempo_bonus;*/
+            /*let mut eval = quiesce(position, turn, alpha, beta, tt, zob);
+            if turn == Color::Black {
+                eval = -eval;
+            }*/
+            debug_assert_eq!(self.boards[ply], Bitboards::new(position));
+            let eval = match self.network {
+                Some(network) => {
+                    debug_assert_eq!(self.nnue[ply], network.refresh(&self.boards[ply]));
+                    network.evaluate(&self.nnue[ply], turn)
+                }
+                None => {
+                    debug_assert_eq!(
+                        self.accumulators[ply],
+                        Accumulator::from_board(&self.boards[ply], self.params)
+                    );
+                    evaluate_with(
+                        &self.boards[ply],
+                        turn,
+                        &self.accumulators[ply],
+                        self.params,
+                    )
+                }
+            };
+            self.tt.insert(
+                hash,
+                TTEntry {
+                    best_move: None,
+                    depth: 0,
+                    value: eval,
+                    bound: Bound::Exact,
+                },
+            );
+            return (None, eval);
+        }
 
-        tt.insert(
-            hash,
-            TTEntry {
-                best_move,
-                depth,
-                value: max_eval,
-                bound,
-            },
-        );
-        (best_move, max_eval)
-    } else {
-        let mut min_eval = WHITE_MATE;
-        let mut best_move = None;
-        let beta_orig = beta;
+        let moves = generate_legal_moves(position, turn, tt_move, &self.killer_moves[ply]);
+
+        if maximizing {
+            let mut max_eval = BLACK_MATE;
+            let mut best_move = None;
+            let alpha_orig = alpha;
 
-        for mv in moves {
-            if stop_flag.load(Ordering::Relaxed) {
-                return (best_move, min_eval); // exit immediately if time is up
+            for mv in moves {
+                if self.stop_flag.load(Ordering::Relaxed) {
+                    return (best_move, max_eval); // exit immediately if time is up
+                }
+                let Some((child, child_depth)) = self.make_move(position, turn, mv, depth, ply)
+                else {
+                    continue;
+                };
+
+                let (_, eval) = self.minimax(
+                    &child,
+                    turn.other(),
+                    child_depth,
+                    ply + 1,
+                    alpha,
+                    beta,
+                    false,
+                );
+                self.history.pop();
+
+                if eval > max_eval {
+                    max_eval = eval;
+                    best_move = Some(mv);
+                }
+
+                alpha = alpha.max(eval);
+                if max_eval >= beta {
+                    self.killer_moves[ply].rotate_right(1);
+                    self.killer_moves[ply][0] = Some(mv);
+                    break; // beta cutoff
+                }
             }
-            let mut child = position.clone();
-            if let Err(_) = child.apply_move(mv) {
-                continue;
+
+            // values coming back from an aborted search are meaningless
+            if self.stop_flag.load(Ordering::Relaxed) {
+                return (best_move, max_eval);
             }
 
-            let (_, mut eval) = minimax(
-                &child,
-                turn.other(),
-                side_to_move,
-                depth - 1,
-                original_depth,
-                alpha,
-                beta,
-                true,
-                tt,
-                zob,
-                killer_moves,
-                stop_flag,
+            // Store in TT
+            let bound = if max_eval <= alpha_orig {
+                Bound::Upper
+            } else if max_eval >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            self.tt.insert(
+                hash,
+                TTEntry {
+                    best_move,
+                    depth,
+                    value: value_to_tt(max_eval, ply),
+                    bound,
+                },
             );
+            (best_move, max_eval)
+        } else {
+            let mut min_eval = WHITE_MATE;
+            let mut best_move = None;
+            let beta_orig = beta;
 
-            if eval < BLACK_MATE + MAX_DEPTH as i32 {
-                eval += 1; // handle mate in n moves
-            }
+            for mv in moves {
+                if self.stop_flag.load(Ordering::Relaxed) {
+                    return (best_move, min_eval); // exit immediately if time is up
+                }
+                let Some((child, child_depth)) = self.make_move(position, turn, mv, depth, ply)
+                else {
+                    continue;
+                };
+
+                let (_, eval) = self.minimax(
+                    &child,
+                    turn.other(),
+                    child_depth,
+                    ply + 1,
+                    alpha,
+                    beta,
+                    true,
+                );
+                self.history.pop();
+
+                if eval < min_eval {
+                    min_eval = eval;
+                    best_move = Some(mv);
+                }
 
-            if eval < min_eval {
-                min_eval = eval;
-                best_move = Some(mv);
+                beta = beta.min(eval);
+                if min_eval <= alpha {
+                    self.killer_moves[ply].rotate_right(1);
+                    self.killer_moves[ply][0] = Some(mv);
+                    break; // alpha cutoff
+                }
             }
 
-            beta = beta.min(eval);
-            if min_eval <= alpha {
-                killer_moves[depth as usize].rotate_right(1);
-                killer_moves[depth as usize][0] = Some(mv);
-                break; // alpha cutoff
+            // values coming back from an aborted search are meaningless
+            if self.stop_flag.load(Ordering::Relaxed) {
+                return (best_move, min_eval);
             }
+
+            // Store in TT
+            let bound = if min_eval <= alpha {
+                Bound::Upper
+            } else if min_eval >= beta_orig {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            self.tt.insert(
+                hash,
+                TTEntry {
+                    best_move,
+                    depth,
+                    value: value_to_tt(min_eval, ply),
+                    bound,
+                },
+            );
+            (best_move, min_eval)
         }
+    }
 
-        // Store in TT
-        let bound = if min_eval <= alpha {
-            Bound::Upper
-        } else if min_eval >= beta_orig {
-            Bound::Lower
+    // Play `mv`, push the child on the history and update its bitboards and accumulators,
+    // returning it with its remaining depth.
+    // Checks are extended as long as the line stays within twice the root depth.
+    fn make_move(
+        &mut self,
+        position: &ChessPosition,
+        turn: Color,
+        mv: Move,
+        depth: usize,
+        ply: usize,
+    ) -> Option<(ChessPosition, usize)> {
+        let mut child = position.clone();
+        if child.apply_move(mv).is_err() {
+            return None;
+        }
+        let delta = MoveDelta::new(position, &mv);
+        self.boards[ply + 1] = self.boards[ply].update(&delta);
+        self.accumulators[ply + 1] = self.accumulators[ply].update(&delta, self.params);
+        if let Some(network) = self.network {
+            let (parents, children) = self.nnue.split_at_mut(ply + 1);
+            network.update(&parents[ply], &mut children[0], &delta);
+        }
+        self.history.push(
+            self.zob.hash_position(&child, turn.other()),
+            is_irreversible(position, &mv),
+        );
+
+        let selective_limit = (2 * self.root_depth).min(MAX_PLY - 1);
+        let extension = if ply + depth < selective_limit && child.is_in_check(turn.other()) {
+            1
         } else {
-            Bound::Exact
+            0
         };
 
-        tt.insert(
-            hash,
-            TTEntry {
-                best_move,
-                depth,
-                value: min_eval,
-                bound,
-            },
-        );
-        (best_move, min_eval)
+        Some((child, depth - 1 + extension))
     }
 }
 
+#[allow(dead_code)]
 fn quiesce(
     position: &ChessPosition,
     turn: Color,
@@ -354,16 +680,16 @@ fn quiesce(
     beta: i32,
     tt: &mut TranspositionTable,
     zob: &mut Zobrist,
+    params: &EvalParams,
 ) -> i32 {
     let hash = zob.hash_position(position, turn);
-    if let Some(entry) = tt.get(&hash) {
-        match entry.bound {
-            Bound::Exact => return entry.value,
-            _ => {}
-        }
+    if let Some(entry) = tt.get(&hash)
+        && let Bound::Exact = entry.bound
+    {
+        return entry.value;
     }
 
-    let mut best_eval = evaluate(position);
+    let mut best_eval = evaluate(position, turn, params);
     if turn == Color::Black {
         best_eval = -best_eval;
     }
@@ -378,10 +704,10 @@ fn quiesce(
     let moves = generate_captures(position, turn);
     for mv in moves {
         let mut child = position.clone();
-        if let Err(_) = child.apply_move(mv) {
+        if child.apply_move(mv).is_err() {
             continue;
         }
-        let mut score = -quiesce(&child, turn.other(), -beta, -alpha, tt, zob);
+        let mut score = -quiesce(&child, turn.other(), -beta, -alpha, tt, zob, params);
         if turn == Color::Black {
             score = -score;
         }
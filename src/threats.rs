@@ -0,0 +1,73 @@
+use crate::bitboard::*;
+use crate::eval::{Attacks, Score, s};
+use crate::params::EvalParams;
+
+// Enemy piece attacked by one of our minors or rooks, indexed by the target's piece type
+pub const THREAT_BY_MINOR: [Score; 6] = [
+    s(4, 22),
+    s(40, 30),
+    s(55, 40),
+    s(65, 85),
+    s(60, 115),
+    s(0, 0),
+];
+pub const THREAT_BY_ROOK: [Score; 6] =
+    [s(2, 30), s(26, 48), s(30, 42), s(0, 28), s(42, 30), s(0, 0)];
+pub const THREAT_BY_KING: Score = s(17, 62);
+// Attacked piece that nothing defends, or that we attack more often than it is defended
+pub const HANGING_PENALTY: Score = s(50, 28);
+// Enemy piece attacked by one of our pawns that is itself safe
+pub const THREAT_BY_SAFE_PAWN: Score = s(115, 70);
+// Enemy piece that a safe pawn push would attack
+pub const THREAT_BY_PAWN_PUSH: Score = s(34, 27);
+
+// Threats `color` makes against the enemy pieces, once `attacks` holds every attack of both sides
+pub fn evaluate_threats(
+    board: &Bitboards,
+    color: usize,
+    attacks: &Attacks,
+    params: &EvalParams,
+) -> Score {
+    let mut score = Score::ZERO;
+    let enemy = 1 - color;
+    let non_pawn_enemies = board.colors[enemy] & !board.pieces[enemy][PAWN];
+
+    // squares defended by an enemy pawn, or more often by the enemy than attacked by us
+    let strongly_protected =
+        attacks.by_piece[enemy][PAWN] | (attacks.twice[enemy] & !attacks.twice[color]);
+    let defended = non_pawn_enemies & strongly_protected;
+    let weak = board.colors[enemy] & !strongly_protected & attacks.all[color];
+
+    if defended | weak != 0 {
+        let minor_attacks = attacks.by_piece[color][KNIGHT] | attacks.by_piece[color][BISHOP];
+        let by_minor = (defended | weak) & minor_attacks;
+        let by_rook = weak & attacks.by_piece[color][ROOK];
+        for piece in PAWN..=QUEEN {
+            score += params.threat_by_minor[piece]
+                * (by_minor & board.pieces[enemy][piece]).count_ones() as i32;
+            score += params.threat_by_rook[piece]
+                * (by_rook & board.pieces[enemy][piece]).count_ones() as i32;
+        }
+
+        if weak & attacks.by_piece[color][KING] != 0 {
+            score += params.threat_by_king;
+        }
+
+        let hanging = weak & (!attacks.all[enemy] | (non_pawn_enemies & attacks.twice[color]));
+        score += params.hanging_penalty * hanging.count_ones() as i32;
+    }
+
+    // squares where our pawns cannot simply be taken
+    let safe = !attacks.all[enemy] | attacks.all[color];
+    let own_pawns = board.pieces[color][PAWN];
+
+    let safe_pawn_targets = pawn_attacks(color, own_pawns & safe) & non_pawn_enemies;
+    score += params.threat_by_safe_pawn * safe_pawn_targets.count_ones() as i32;
+
+    let pushes =
+        pawn_pushes(color, own_pawns, board.occupied) & !attacks.by_piece[enemy][PAWN] & safe;
+    let push_targets = pawn_attacks(color, pushes) & non_pawn_enemies;
+    score += params.threat_by_pawn_push * push_targets.count_ones() as i32;
+
+    score
+}
@@ -0,0 +1,241 @@
+use crate::bitboard::Bitboards;
+use crate::eval::evaluate_board;
+use crate::params::EvalParams;
+use crabchess::prelude::*;
+use std::fs;
+use std::thread;
+use std::time::Instant;
+
+/// Options of the `tune` subcommand
+pub struct TuneOptions {
+    pub dataset: String,
+    pub output: String,
+    pub passes: usize,
+    pub threads: usize,
+}
+
+impl TuneOptions {
+    // tune <dataset> [--output <file>] [--passes <n>] [--threads <n>]
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = TuneOptions {
+            dataset: String::new(),
+            output: String::from("tuned.toml"),
+            passes: 100,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--output" => options.output = value()?.clone(),
+                "--passes" => options.passes = value()?.parse().map_err(|e| format!("{}", e))?,
+                "--threads" => options.threads = value()?.parse().map_err(|e| format!("{}", e))?,
+                _ if options.dataset.is_empty() => options.dataset = arg.clone(),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if options.dataset.is_empty() {
+            return Err(String::from(
+                "Usage: tune <dataset> [--output <file>] [--passes <n>] [--threads <n>]",
+            ));
+        }
+        options.threads = options.threads.max(1);
+        Ok(options)
+    }
+}
+
+/// A quiet position and the result of the game it comes from, 1.0 when White won
+pub struct Entry {
+    pub board: Bitboards,
+    pub turn: Color,
+    pub result: f64,
+}
+
+// Texel tuning: fit the sigmoid scaling K to the starting parameters, then nudge every
+// weight by one as long as that lowers the mean squared error against the game results
+pub fn run(options: &TuneOptions, params: EvalParams) -> Result<(), String> {
+    let start = Instant::now();
+    let entries = load_dataset(&options.dataset)?;
+    if entries.is_empty() {
+        return Err(format!("{}: no usable positions", options.dataset));
+    }
+    println!(
+        "Loaded {} positions in {:.1}s",
+        entries.len(),
+        start.elapsed().as_secs_f64()
+    );
+
+    let evals = evaluations(&entries, &params, options.threads);
+    let k = fit_k(&entries, &evals);
+    let mut best = mean_squared_error(&entries, &evals, k);
+    println!("K = {:.4}, starting error {:.6}", k, best);
+
+    let mut weights = params.to_weights();
+    for pass in 1..=options.passes {
+        let mut changed = 0;
+        for i in 0..weights.len() {
+            for delta in [1, -1] {
+                weights[i] += delta;
+                let candidate = EvalParams::from_weights(&weights);
+                let evals = evaluations(&entries, &candidate, options.threads);
+                let error = mean_squared_error(&entries, &evals, k);
+                if error < best {
+                    best = error;
+                    changed += 1;
+                    break;
+                }
+                weights[i] -= delta;
+            }
+        }
+
+        // keep whatever was gained so far should the run be interrupted
+        let tuned = EvalParams::from_weights(&weights);
+        fs::write(&options.output, tuned.to_toml())
+            .map_err(|e| format!("{}: {}", options.output, e))?;
+        println!(
+            "Pass {}: error {:.6}, {} weights changed, {:.0}s elapsed",
+            pass,
+            best,
+            changed,
+            start.elapsed().as_secs_f64()
+        );
+
+        if changed == 0 {
+            break;
+        }
+    }
+
+    println!("Tuned parameters written to {}", options.output);
+    Ok(())
+}
+
+// One position per line: a FEN followed by the result, either as "1-0", "0-1", "1/2-1/2"
+// or as a number in brackets such as [0.5], or a "<fen> | <score> | <result>" line written
+// by `datagen`
+fn load_dataset(path: &str) -> Result<Vec<Entry>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut entries = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_entry(line) {
+            Some(entry) => entries.push(entry),
+            None => eprintln!("{}:{}: skipping unreadable line", path, number + 1),
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn parse_entry(line: &str) -> Option<Entry> {
+    let (line, result) = match line.split_once('|') {
+        Some((fen, rest)) => (fen, rest.rsplit('|').next()?.trim().parse().ok()?),
+        None => (line, parse_result(line)?),
+    };
+
+    // piece placement, side to move, castling and en passant, clocks when present
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    let clocks = fields[4..]
+        .iter()
+        .take(2)
+        .take_while(|field| field.parse::<u32>().is_ok())
+        .count();
+    let mut fen = fields[..4 + clocks].join(" ");
+    if clocks == 0 {
+        fen.push_str(" 0 1");
+    }
+
+    let position = ChessPosition::from_fen(&fen).ok()?;
+    Some(Entry {
+        board: Bitboards::new(&position),
+        turn: if fields[1] == "b" {
+            Color::Black
+        } else {
+            Color::White
+        },
+        result,
+    })
+}
+
+fn parse_result(line: &str) -> Option<f64> {
+    if line.contains("1/2-1/2") {
+        Some(0.5)
+    } else if line.contains("1-0") {
+        Some(1.0)
+    } else if line.contains("0-1") {
+        Some(0.0)
+    } else {
+        let open = line.rfind('[')?;
+        let close = line[open..].find(']')? + open;
+        line[open + 1..close].trim().parse().ok()
+    }
+}
+
+// Static evaluations of every entry, split over `threads` scoped threads. Each thread
+// starts with an empty pawn hash table, so no entry computed with other weights is reused.
+fn evaluations(entries: &[Entry], params: &EvalParams, threads: usize) -> Vec<i32> {
+    let chunk_size = entries.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|entry| evaluate_board(&entry.board, entry.turn, params))
+                        .collect::<Vec<i32>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+// Expected score for White of a centipawn evaluation
+fn sigmoid(eval: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0))
+}
+
+fn mean_squared_error(entries: &[Entry], evals: &[i32], k: f64) -> f64 {
+    let total: f64 = entries
+        .iter()
+        .zip(evals)
+        .map(|(entry, &eval)| (entry.result - sigmoid(eval, k)).powi(2))
+        .sum();
+    total / entries.len() as f64
+}
+
+// K minimizing the error of the current evaluations, refined one decimal at a time
+fn fit_k(entries: &[Entry], evals: &[i32]) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = mean_squared_error(entries, evals, best_k);
+    let mut step = 1.0;
+
+    for _ in 0..4 {
+        let center = best_k;
+        for i in -10..=10 {
+            let k = center + i as f64 * step / 10.0;
+            if k <= 0.0 {
+                continue;
+            }
+            let error = mean_squared_error(entries, evals, k);
+            if error < best_error {
+                best_error = error;
+                best_k = k;
+            }
+        }
+        step /= 10.0;
+    }
+
+    best_k
+}
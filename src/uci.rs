@@ -1,4 +1,7 @@
+use crate::moves::generate_legal_moves;
 use crabchess::prelude::*;
+use std::fmt;
+use std::time::Duration;
 
 pub trait UciFormat {
     fn uci(&self) -> String;
@@ -64,107 +67,191 @@ pub fn square_to_uci(sq: Square) -> String {
     format!("{}{}", file, rank)
 }
 
-pub fn move_from_uci(position: &ChessPosition, uci_move: &str) -> Option<Move> {
-    let from_sq = square_from_uci(&uci_move[0..2])?;
-    let to_sq = square_from_uci(&uci_move[2..4])?;
-
-    let (piece_type, piece_color) = position.get(from_sq).map(|p| (p.piece_type, p.color))?;
-
-    let is_capture = position.get(to_sq).is_some();
-
-    // en passant: pawn moves diagonally to an empty square
-    if !is_capture {
-        if let Some(p) = position.get(from_sq) {
-            if p.piece_type == Type::Pawn && from_sq.file() != to_sq.file() {
-                return Some(Move::EnPassant {
-                    initial_square: from_sq,
-                    capture_square: Square(to_sq.file(), from_sq.rank()),
-                    final_square: to_sq,
-                    piece_color: p.color,
-                    timer_update: None,
-                });
-            }
+/// Why a move of a `position` command could not be played
+#[derive(Debug, PartialEq, Eq)]
+pub enum UciMoveError {
+    // not written as <from><to>[promotion], as in "e2e4" or "e7e8q"
+    Malformed(String),
+    // well written but not legal in the position
+    Illegal(String),
+}
+
+impl fmt::Display for UciMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciMoveError::Malformed(text) => write!(f, "Malformed move {}", text),
+            UciMoveError::Illegal(text) => write!(f, "Illegal move {}", text),
         }
     }
+}
+
+// The legal move written `text` in long algebraic notation, castling as the king's move
+pub fn move_from_uci(
+    position: &ChessPosition,
+    turn: Color,
+    text: &str,
+) -> Result<Move, UciMoveError> {
+    let well_formed = text.is_ascii()
+        && matches!(text.len(), 4 | 5)
+        && square_from_uci(&text[0..2]).is_some()
+        && square_from_uci(&text[2..4]).is_some()
+        && text[4..].chars().all(|c| "qrbn".contains(c));
+    if !well_formed {
+        return Err(UciMoveError::Malformed(text.to_string()));
+    }
 
-    // adhoc handling of castling
-    match (uci_move, piece_type) {
-        ("e1g1", Type::King) => {
-            return Some(Move::Castle {
-                color: Color::White,
-                side: Side::Kingside,
-                timer_update: None,
-            })
-        }
-        ("e1c1", Type::King) => {
-            return Some(Move::Castle {
-                color: Color::White,
-                side: Side::Queenside,
-                timer_update: None,
-            })
-        }
-        ("e8g8", Type::King) => {
-            return Some(Move::Castle {
-                color: Color::Black,
-                side: Side::Kingside,
-                timer_update: None,
-            })
+    generate_legal_moves(position, turn, None, &[None; 2])
+        .into_iter()
+        .find(|mv| mv.uci() == text)
+        .ok_or_else(|| UciMoveError::Illegal(text.to_string()))
+}
+
+/// Position of a `position` command: where the game starts and the legal moves played
+/// from there
+pub struct PositionCommand {
+    pub start: ChessPosition,
+    pub turn: Color,
+    pub moves: Vec<Move>,
+    // the first move that could not be played, the moves after it are dropped as they
+    // would be played from the wrong position
+    pub error: Option<UciMoveError>,
+}
+
+impl PositionCommand {
+    // position [startpos | fen <fen>] [moves <move>...]
+    pub fn parse<'a>(parts: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut parts = parts.peekable();
+        let (start, turn) = match parts.next() {
+            Some("startpos") => (ChessPosition::new(), Color::White),
+            Some("fen") => {
+                let mut fields = Vec::new();
+                while let Some(field) = parts.next_if(|token| *token != "moves") {
+                    fields.push(field);
+                }
+                let fen = fields.join(" ");
+                let start = ChessPosition::from_fen(&fen)
+                    .map_err(|e| format!("Invalid FEN {}: {}", fen, e))?;
+                let turn = if fields.get(1) == Some(&"b") {
+                    Color::Black
+                } else {
+                    Color::White
+                };
+                (start, turn)
+            }
+            _ => return Err(String::from("position needs startpos or fen")),
+        };
+
+        let mut command = PositionCommand {
+            start,
+            turn,
+            moves: Vec::new(),
+            error: None,
+        };
+        if parts.next() != Some("moves") {
+            return Ok(command);
         }
-        ("e8c8", Type::King) => {
-            return Some(Move::Castle {
-                color: Color::Black,
-                side: Side::Queenside,
-                timer_update: None,
-            })
+        let mut position = command.start.clone();
+        let mut turn = command.turn;
+        for text in parts {
+            match move_from_uci(&position, turn, text) {
+                Ok(mv) => {
+                    position.apply_move(mv).unwrap();
+                    turn = turn.other();
+                    command.moves.push(mv);
+                }
+                Err(e) => {
+                    command.error = Some(e);
+                    break;
+                }
+            }
         }
-        _ => {}
-    };
+        Ok(command)
+    }
+}
 
-    // Handle promotions
-    let promotion = if uci_move.len() == 5 {
-        match &uci_move[4..5] {
-            "q" => Some(Type::Queen),
-            "r" => Some(Type::Rook),
-            "b" => Some(Type::Bishop),
-            "n" => Some(Type::Knight),
-            _ => None,
-        }
-    } else {
-        None
+pub fn square_from_uci(s: &str) -> Option<Square> {
+    let mut chars = s.chars();
+    let (Some(file), Some(rank), None) = (chars.next(), chars.next(), chars.next()) else {
+        return None;
     };
+    Some(Square(
+        File::from_char(file).ok()?,
+        Rank::from_char(&rank).ok()?,
+    ))
+}
 
-    if promotion.is_some() {
-        Some(Move::PawnPromotion {
-            initial_square: from_sq,
-            piece_color,
-            final_square: to_sq,
-            is_capture,
-            new_type: match promotion.unwrap() {
-                Type::Queen => PromotedType::Queen,
-                Type::Rook => PromotedType::Rook,
-                Type::Bishop => PromotedType::Bishop,
-                Type::Knight => PromotedType::Knight,
-                _ => return None, // Invalid promotion type
-            },
-            timer_update: None,
-        })
-    } else {
-        Some(Move::Standard {
-            initial_square: from_sq,
-            piece_type,
-            piece_color,
-            final_square: to_sq,
-            is_capture,
-            timer_update: None,
-        })
+// Split "setoption name <name> value <value>" into its name and value
+pub fn parse_setoption<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<(String, String)> {
+    if parts.next() != Some("name") {
+        return None;
     }
+
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    let mut in_value = false;
+    for token in parts {
+        if token == "value" && !in_value {
+            in_value = true;
+        } else if in_value {
+            value.push(token);
+        } else {
+            name.push(token);
+        }
+    }
+
+    Some((name.join(" "), value.join(" ")))
 }
 
-pub fn square_from_uci(s: &str) -> Option<Square> {
-    if s.len() != 2 {
-        return None;
+/// Limits of a single `go` command
+#[derive(Clone, Copy)]
+pub struct SearchLimits {
+    // None when the search only stops on its depth, mate or node limit
+    pub time: Option<Duration>,
+    pub depth: Option<usize>,
+    pub mate: Option<i32>,
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    // The time for the move comes from movetime, or from the clock of the side to move.
+    // Without either, a fixed 2990 ms applies unless a depth, mate or node limit is given.
+    pub fn parse<'a>(mut parts: impl Iterator<Item = &'a str>, turn: Color) -> Self {
+        let mut limits = SearchLimits {
+            time: None,
+            depth: None,
+            mate: None,
+            nodes: None,
+        };
+        let (mut movetime, mut clock, mut increment, mut moves_to_go) = (None, None, None, None);
+        let side = if turn == Color::White { "w" } else { "b" };
+
+        while let Some(token) = parts.next() {
+            let mut value = || parts.next().and_then(|n| n.parse::<u64>().ok());
+            match token {
+                "depth" => limits.depth = value().map(|n| n as usize),
+                "mate" => limits.mate = value().map(|n| n as i32),
+                "nodes" => limits.nodes = value(),
+                "movetime" => movetime = value(),
+                "movestogo" => moves_to_go = value(),
+                _ if token.strip_suffix("time") == Some(side) => clock = value(),
+                _ if token.strip_suffix("inc") == Some(side) => increment = value(),
+                _ => {}
+            }
+        }
+
+        limits.time = match (movetime, clock) {
+            (Some(ms), _) => Some(Duration::from_millis(ms)),
+            // an even share of the clock over the moves to go plus half the increment,
+            // kept 50 ms away from the flag
+            (None, Some(ms)) => {
+                let share = ms / moves_to_go.unwrap_or(30).max(1) + increment.unwrap_or(0) / 2;
+                Some(Duration::from_millis(
+                    share.min(ms.saturating_sub(50)).max(1),
+                ))
+            }
+            _ if limits.depth.is_some() || limits.mate.is_some() || limits.nodes.is_some() => None,
+            _ => Some(Duration::from_millis(2990)),
+        };
+        limits
     }
-    let file = File::from_char(s.chars().nth(0).unwrap()).unwrap();
-    let rank = Rank::from_char(&s.chars().nth(1).unwrap()).unwrap();
-    Some(Square(file, rank))
 }
@@ -2,30 +2,51 @@ use crate::eval::SquareIdx;
 use crabchess::prelude::*;
 use rand::prelude::*;
 use rand::rng;
+use rand::rngs::StdRng;
 
 pub struct Zobrist {
     table: [[[u64; 64]; 2]; 6], // piece_type × color × square
     side_to_move: u64,          // random 64-bit number for side to move
+    castling: [u64; 4],         // K, Q, k, q
+    en_passant: [u64; 8],       // by file
+}
+
+impl Default for Zobrist {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Zobrist {
     pub fn new() -> Self {
-        let mut rng = rng();
+        Self::from_rng(&mut rng())
+    }
+
+    // Same keys on every run, so that searches and their node counts can be reproduced
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(rng: &mut impl Rng) -> Self {
         let mut table = [[[0u64; 64]; 2]; 6];
 
-        for pt_idx in 0..6 {
-            for color_idx in 0..2 {
-                for sq in 0..64 {
-                    table[pt_idx][color_idx][sq] = rng.random::<u64>();
+        for piece_table in table.iter_mut() {
+            for color_table in piece_table.iter_mut() {
+                for key in color_table.iter_mut() {
+                    *key = rng.random::<u64>();
                 }
             }
         }
 
         let side_to_move = rng.random::<u64>();
+        let castling = rng.random::<[u64; 4]>();
+        let en_passant = rng.random::<[u64; 8]>();
 
         Zobrist {
             table,
             side_to_move,
+            castling,
+            en_passant,
         }
     }
 
@@ -55,6 +76,52 @@ impl Zobrist {
             h ^= self.side_to_move;
         }
 
+        // Castling rights and en passant are only available from the FEN. The en passant
+        // file counts only when a pawn stands next to the pawn that just moved, as the same
+        // position is otherwise reached again without it.
+        let fen = pos.fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        for (i, right) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if fields[2].contains(right) {
+                h ^= self.castling[i];
+            }
+        }
+        if let Some(file) = fields[3].bytes().next().filter(u8::is_ascii_lowercase) {
+            let file = (file - b'a') as usize;
+            let rank = if turn == Color::White {
+                Rank::Five
+            } else {
+                Rank::Four
+            };
+            let can_take = [file.wrapping_sub(1), file + 1].iter().any(|&f| {
+                f < 8
+                    && *pos.get(Square(File::all()[f], rank))
+                        == Some(Piece {
+                            piece_type: Type::Pawn,
+                            color: turn,
+                        })
+            });
+            if can_take {
+                h ^= self.en_passant[file];
+            }
+        }
+
+        h
+    }
+
+    // Key over the pawns alone, `pawns` holding the white then the black pawn bitboard
+    pub fn hash_pawns(&self, pawns: [u64; 2]) -> u64 {
+        let mut h = 0u64;
+
+        for (color_idx, &bitboard) in pawns.iter().enumerate() {
+            let mut bitboard = bitboard;
+            while bitboard != 0 {
+                let sq = bitboard.trailing_zeros() as usize;
+                h ^= self.table[0][color_idx][sq];
+                bitboard &= bitboard - 1;
+            }
+        }
+
         h
     }
 }
@@ -0,0 +1,242 @@
+use copperfish::book::{Book, decode_move, encode_move, key};
+use copperfish::uci::UciFormat;
+use copperfish::{book, moves, uci};
+use crabchess::prelude::*;
+
+fn position(fen: &str) -> ChessPosition {
+    ChessPosition::from_fen(fen).unwrap()
+}
+
+// A book file of `(fen, uci move, weight)` entries
+fn write_book(name: &str, moves: &[(&str, &str, u16)]) -> Book {
+    let mut entries: Vec<(u64, u16, u16)> = moves
+        .iter()
+        .map(|&(fen, uci, weight)| {
+            let pos = position(fen);
+            let turn = if fen.contains(" w ") {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let mv = uci::move_from_uci(&pos, turn, uci).unwrap();
+            (key(&pos), encode_move(&mv), weight)
+        })
+        .collect();
+    entries.sort_by_key(|&(key, ..)| key);
+
+    let mut bytes = Vec::new();
+    for (key, mv, weight) in entries {
+        bytes.extend(key.to_be_bytes());
+        bytes.extend(mv.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+    }
+    let path = std::env::temp_dir().join(format!("copperfish-{}-{}.bin", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let book = Book::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    book.unwrap()
+}
+
+#[test]
+fn polyglot_keys() {
+    // reference keys of the Polyglot book format specification
+    let keys = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            0x463b96181691fc9c,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            0x823c9b50fd114196,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            0x0756b94461c50fb0,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+            0x662fafb965db29d4,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            0x22a48b5a8e47ff78,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 1 3",
+            0x652a607ca3f242c1,
+        ),
+        (
+            "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 2 4",
+            0x00fdd303c946bdd9,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+            0x3c8123ea7b067637,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 1 4",
+            0x5c3f9b829b279560,
+        ),
+    ];
+    for (fen, expected) in keys {
+        assert_eq!(key(&position(fen)), expected, "{}", fen);
+    }
+}
+
+#[test]
+fn book_move_encoding() {
+    // castling is stored as the king taking its own rook
+    let pos = position("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+    assert_eq!(decode_move(&pos, 0o0407), "e1g1");
+    assert_eq!(decode_move(&pos, 0o0400), "e1c1");
+    // a rook on e1 taking on h1 is not castling
+    let pos = position("3k4/8/8/8/8/8/8/K3R2n w - - 0 1");
+    assert_eq!(decode_move(&pos, 0o0407), "e1h1");
+    // promotion piece in bits 12-14
+    let pos = position("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1");
+    assert_eq!(decode_move(&pos, 4 << 12 | 0o1606), "g2g1q");
+    assert_eq!(decode_move(&pos, 1 << 12 | 0o1507), "f2h1n");
+
+    for (fen, turn) in [
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            Color::White,
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            Color::Black,
+        ),
+        ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", Color::Black),
+        ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1", Color::White),
+    ] {
+        let pos = position(fen);
+        for mv in moves::generate_legal_moves(&pos, turn, None, &[None; 2]) {
+            assert_eq!(decode_move(&pos, encode_move(&mv)), mv.uci(), "{}", fen);
+        }
+    }
+}
+
+#[test]
+fn book_pick() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let book = write_book(
+        "pick",
+        &[
+            (start, "e2e4", 3),
+            (start, "d2d4", 1),
+            (start, "g1f3", 0),
+            (after_e4, "c7c5", 2),
+        ],
+    );
+    assert_eq!(book.len(), 4);
+
+    let pos = position(start);
+    let heaviest = book.pick(&pos, Color::White, 20, true).unwrap();
+    assert_eq!(heaviest.uci(), "e2e4");
+    // a weighted random pick never plays a move of weight 0
+    for _ in 0..100 {
+        let mv = book.pick(&pos, Color::White, 20, false).unwrap().uci();
+        assert!(mv == "e2e4" || mv == "d2d4", "{}", mv);
+    }
+    assert_eq!(book.moves(&pos, Color::White).len(), 3);
+
+    let pos = position(after_e4);
+    assert_eq!(
+        book.pick(&pos, Color::Black, 20, true).unwrap().uci(),
+        "c7c5"
+    );
+    // past the book depth or out of the book
+    assert!(book.pick(&pos, Color::Black, 1, true).is_none());
+    let pos = position("rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1");
+    assert!(book.pick(&pos, Color::Black, 20, false).is_none());
+}
+
+// Builds a book from the PGN `text` and reads it back
+fn build_book(name: &str, text: &str, max_ply: usize, min_games: u32) -> Book {
+    let dir = std::env::temp_dir();
+    let pgn = dir.join(format!("copperfish-{}-{}.pgn", name, std::process::id()));
+    let output = dir.join(format!("copperfish-{}-{}.bin", name, std::process::id()));
+    std::fs::write(&pgn, text).unwrap();
+    let options = book::BuildOptions {
+        output: output.to_str().unwrap().to_string(),
+        inputs: vec![pgn.to_str().unwrap().to_string()],
+        max_ply,
+        min_games,
+    };
+    book::build(&options).unwrap();
+    let book = Book::load(&options.output);
+    std::fs::remove_file(&pgn).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    book.unwrap()
+}
+
+// Book moves of a position as UCI moves and weights, heaviest first
+fn book_moves(book: &Book, fen: &str) -> Vec<(String, u16)> {
+    let turn = if fen.contains(" w ") {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let mut moves: Vec<(String, u16)> = book
+        .moves(&position(fen), turn)
+        .into_iter()
+        .map(|(mv, weight)| (mv.uci(), weight))
+        .collect();
+    moves.sort_by_key(|(uci, weight)| (std::cmp::Reverse(*weight), uci.clone()));
+    moves
+}
+
+#[test]
+fn book_build_from_pgn() {
+    let text = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 Nc6 1-0\n\n\
+                [Result \"0-1\"]\n\n1. e4 c5 2. Nf3 0-1\n\n\
+                [Result \"1/2-1/2\"]\n\n1. d4 d5 2. c4 1/2-1/2\n\n\
+                [Result \"*\"]\n\n1. c4 *\n";
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+    let e4_e5 = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+    let d4 = "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1";
+    let e4_c5 = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+
+    // 2 per win and 1 per draw of the side to move, losses and unfinished games left out
+    let book = build_book("build", text, 3, 1);
+    let weights = |moves: &[(&str, u16)]| -> Vec<(String, u16)> {
+        moves
+            .iter()
+            .map(|&(uci, weight)| (uci.to_string(), weight))
+            .collect()
+    };
+    assert_eq!(book.len(), 6);
+    assert_eq!(
+        book_moves(&book, start),
+        weights(&[("e2e4", 2), ("d2d4", 1)])
+    );
+    assert_eq!(book_moves(&book, d4), weights(&[("d7d5", 1)]));
+    assert_eq!(book_moves(&book, e4), weights(&[("c7c5", 2)]));
+    assert_eq!(book_moves(&book, e4_e5), weights(&[("g1f3", 2)]));
+    assert_eq!(book_moves(&book, e4_c5), weights(&[]));
+
+    let book = build_book("min-games", text, 3, 2);
+    assert_eq!(book.len(), 1);
+    assert_eq!(book_moves(&book, start), weights(&[("e2e4", 2)]));
+}
+
+#[test]
+fn book_weights_scaled_to_16_bits() {
+    // 40000 wins with one move and 10000 with another keep their 4 to 1 ratio, the moves of
+    // other positions their weight
+    let stats = std::collections::HashMap::from([
+        ((1, 10), [40000, 0, 5]),
+        ((1, 11), [10000, 0, 0]),
+        ((1, 12), [0, 1, 0]),
+        ((2, 10), [3, 4, 1]),
+        ((2, 11), [0, 0, 7]),
+    ]);
+    let entries: Vec<(u64, u16, u16)> = book::weigh(stats, 1)
+        .iter()
+        .map(|entry| (entry.key, entry.mv, entry.weight))
+        .collect();
+    assert_eq!(entries, [(1, 10, 65535), (1, 11, 16383), (2, 10, 10)]);
+}
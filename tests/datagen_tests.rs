@@ -0,0 +1,69 @@
+use copperfish::moves::halfmove_clock;
+use copperfish::params::EvalParams;
+use copperfish::search::*;
+use copperfish::zobrist::Zobrist;
+use copperfish::{datagen, tune};
+use crabchess::prelude::*;
+use std::sync::{Arc, atomic::AtomicBool};
+
+// Score of a short search, the way datagen records it
+fn search_score(fen: &str) -> i32 {
+    let position = ChessPosition::from_fen(fen).unwrap();
+    let turn = if fen.contains(" w ") {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let params = EvalParams::default();
+    let zob = Zobrist::with_seed(1);
+    let mut tt = TranspositionTable::new();
+    let mut history = History::new(
+        zob.hash_position(&position, turn),
+        halfmove_clock(&position),
+    );
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let mut searcher = Searcher::new(
+        &mut tt,
+        &zob,
+        &params,
+        None,
+        &mut history,
+        Contempt::new(),
+        &stop_flag,
+    );
+    let mut score = 0;
+    for depth in 1..=3 {
+        score = searcher
+            .aspiration_search(&position, turn, score, depth, 25)
+            .unwrap()
+            .1;
+    }
+    score
+}
+
+#[test]
+fn datagen_line_format() {
+    let fen = "4k3/8/8/8/8/8/3QK3/8 b - - 3 40";
+    let line = datagen::format_line(fen, 812, 1.0);
+    assert_eq!(line, "4k3/8/8/8/8/8/3QK3/8 b - - 3 40 | 812 | 1.0");
+    assert_eq!(
+        datagen::format_line(fen, -15, 0.5),
+        format!("{} | -15 | 0.5", fen)
+    );
+    assert_eq!(
+        datagen::format_line(fen, 0, 0.0),
+        format!("{} | 0 | 0.0", fen)
+    );
+
+    let entry = tune::parse_entry(&line).unwrap();
+    assert_eq!((entry.turn, entry.result), (Color::Black, 1.0));
+}
+
+#[test]
+fn datagen_scores_are_from_white_point_of_view() {
+    // White a queen up is a positive score whichever side is to move, matching a 1.0 result
+    assert!(search_score("4k3/8/8/8/8/8/3QK3/8 b - - 0 1") > 500);
+    assert!(search_score("4k3/8/8/8/8/8/3QK3/8 w - - 0 1") > 500);
+    assert!(search_score("4k3/3q4/8/8/8/8/4K3/8 w - - 0 1") < -500);
+    assert!(search_score("4k3/3q4/8/8/8/8/4K3/8 b - - 0 1") < -500);
+}
@@ -0,0 +1,68 @@
+use copperfish::bitboard::*;
+use copperfish::endgame;
+use copperfish::endgame::{KNOWN_WIN, SCALE_NORMAL};
+use copperfish::params::EvalParams;
+use crabchess::prelude::ChessPosition;
+
+fn board(fen: &str) -> Bitboards {
+    Bitboards::new(&ChessPosition::from_fen(fen).unwrap())
+}
+
+// Specialized evaluation from White's point of view
+fn evaluate(fen: &str) -> Option<i32> {
+    let turn = if fen.contains(" w ") { WHITE } else { BLACK };
+    endgame::evaluate(&board(fen), turn, &EvalParams::default())
+}
+
+#[test]
+fn kbnk_prefers_the_bishop_colored_corner() {
+    // a light-squared bishop mates on h1 or a8, not a1 or h8, kings as close in both
+    let right = evaluate("8/8/8/4N3/4B3/5K2/8/7k b - - 0 1").unwrap();
+    let wrong = evaluate("8/8/8/4N3/4B3/2K5/8/k7 b - - 0 1").unwrap();
+    assert!(right > wrong, "{} <= {}", right, wrong);
+    assert!(wrong > KNOWN_WIN);
+
+    // the same from Black's side, the a8 corner rather than h8
+    let right = evaluate("K7/8/2k5/8/3n4/3b4/8/8 w - - 0 1").unwrap();
+    let wrong = evaluate("7K/8/5k2/8/3n4/3b4/8/8 w - - 0 1").unwrap();
+    assert!(right < wrong, "{} >= {}", right, wrong);
+    assert!(wrong < -KNOWN_WIN);
+}
+
+#[test]
+fn krkp_drawn_with_a_supported_pawn() {
+    // the pawn queens under its king's protection with the white king far away
+    let drawn = evaluate("K7/8/8/8/8/8/2kp4/7R w - - 0 1").unwrap();
+    assert!(drawn.abs() < 100, "{}", drawn);
+    // the same pawn left alone is lost
+    let won = evaluate("4k3/8/8/8/8/8/3p4/K6R w - - 0 1").unwrap();
+    assert!(won > 400, "{}", won);
+    // mirrored colors
+    let drawn = evaluate("7r/2KP4/8/8/8/8/8/k7 b - - 0 1").unwrap();
+    assert!(drawn.abs() < 100, "{}", drawn);
+}
+
+#[test]
+fn kbk_scales_to_a_draw() {
+    let params = EvalParams::default();
+    for (fen, strong) in [
+        ("8/8/8/4k3/8/8/8/2B1K3 w - - 0 1", WHITE),
+        ("8/8/8/4k3/8/8/8/2b1K3 b - - 0 1", BLACK),
+        ("8/8/8/4k3/8/8/8/1N2K3 w - - 0 1", WHITE),
+    ] {
+        assert_eq!(
+            endgame::scale_factor(&board(fen), strong, &params),
+            0,
+            "{}",
+            fen
+        );
+        assert_eq!(evaluate(fen), None, "{}", fen);
+    }
+    // bishops of both colors can mate
+    let fen = "8/8/8/4k3/8/8/8/2B1KB2 w - - 0 1";
+    assert_eq!(
+        endgame::scale_factor(&board(fen), WHITE, &params),
+        SCALE_NORMAL
+    );
+    assert!(evaluate(fen).unwrap() > KNOWN_WIN);
+}
@@ -0,0 +1,93 @@
+use copperfish::epd::*;
+use copperfish::params::EvalParams;
+use copperfish::uci::move_from_uci;
+use crabchess::prelude::*;
+
+const BACK_RANK: &str = "6k1/5ppp/8/8/8/8/8/R5K1 w - -";
+
+#[test]
+fn epd_parse_line() {
+    let test = parse_line(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - \
+         bm Bb5 Bc4; am g4; id \"open.001\"; hmvc 2; fmvn 3;",
+    )
+    .unwrap();
+    assert_eq!(test.id, "open.001");
+    assert_eq!(test.turn, Color::White);
+    assert_eq!(test.best, ["Bb5", "Bc4"]);
+    assert_eq!(test.avoid, ["g4"]);
+    assert!(test.position.fen().ends_with(" 2 3"));
+
+    // the move counters default to a fresh clock on move one
+    let test = parse_line("4k3/8/8/8/8/8/8/R3K3 b - - am Kd7").unwrap();
+    assert_eq!(test.turn, Color::Black);
+    assert!(test.best.is_empty());
+    assert!(test.id.is_empty());
+    assert!(test.position.fen().ends_with(" 0 1"));
+
+    assert!(parse_line("4k3/8/8/8 w").is_err());
+    assert!(parse_line(&format!("{} id \"no moves\";", BACK_RANK)).is_err());
+    assert!(parse_line("4k3/8/8/8/8/8/8/R3K3 w - - bm Ra8; hmvc x;").is_err());
+}
+
+#[test]
+fn epd_split_operations() {
+    assert_eq!(
+        split_operations("bm e4; id \"a; b\"; c0 \"x\";"),
+        ["bm e4", " id \"a; b\"", " c0 \"x\"", ""]
+    );
+    assert_eq!(split_operations("bm e4"), ["bm e4"]);
+
+    // a semicolon in the id does not cut the operations that follow it
+    let test = parse_line(&format!("{} id \"mate; back rank\"; bm Ra8#;", BACK_RANK)).unwrap();
+    assert_eq!(test.id, "mate; back rank");
+    assert_eq!(test.best, ["Ra8#"]);
+}
+
+#[test]
+fn epd_best_move_san_and_uci() {
+    let mv = |test: &Test, text: &str| move_from_uci(&test.position, test.turn, text).unwrap();
+
+    for operations in ["bm Ra8#;", "bm Ra8;", "bm a1a8;", "bm Rb1 Ra8#;"] {
+        let test = parse_line(&format!("{} {}", BACK_RANK, operations)).unwrap();
+        assert!(is_solved(&test, mv(&test, "a1a8")), "{}", operations);
+        assert!(!is_solved(&test, mv(&test, "a1a7")), "{}", operations);
+    }
+
+    // an avoid move fails the test however it is written
+    for operations in ["am Ra8#;", "am a1a8;"] {
+        let test = parse_line(&format!("{} {}", BACK_RANK, operations)).unwrap();
+        assert!(!is_solved(&test, mv(&test, "a1a8")), "{}", operations);
+        assert!(is_solved(&test, mv(&test, "g1f2")), "{}", operations);
+    }
+}
+
+#[test]
+fn epd_run_tally() {
+    let path = std::env::temp_dir().join(format!("copperfish-{}.epd", std::process::id()));
+    let suite = [
+        "# comments and blank lines are skipped".to_string(),
+        String::new(),
+        format!("{} bm Ra8#; id \"san\";", BACK_RANK),
+        format!("{} bm a1a8; id \"uci\";", BACK_RANK),
+        format!("{} am Ra8#; id \"avoid\";", BACK_RANK),
+        "not an epd line".to_string(),
+        format!("{} bm Rb1;", BACK_RANK),
+    ];
+    std::fs::write(&path, suite.join("\n")).unwrap();
+
+    let args = [path.to_str().unwrap(), "--depth", "2"].map(str::to_string);
+    let tally = run(
+        &EpdOptions::parse(&args).unwrap(),
+        EvalParams::default(),
+        None,
+    );
+    std::fs::remove_file(&path).unwrap();
+    let tally = tally.unwrap();
+
+    // the malformed line is reported, not counted, and a test without an id is named by
+    // its line number
+    assert_eq!((tally.solved, tally.total), (2, 4));
+    assert_eq!(tally.failed, ["avoid", "#7"]);
+    assert!(tally.nodes > 0);
+}
@@ -0,0 +1,211 @@
+use copperfish::bitboard::{BLACK, Bitboards, WHITE};
+use copperfish::endgame::SCALE_NORMAL;
+use copperfish::eval::{
+    Accumulator, MAX_PHASE, MoveDelta, Score, Terms, evaluate, evaluate_with, s,
+};
+use copperfish::moves::generate_legal_moves;
+use copperfish::params::EvalParams;
+use crabchess::prelude::*;
+
+const VALUES: [i32; 11] = [0, 1, -1, 2, -2, 100, -100, 950, -950, 32767, -32768];
+
+#[test]
+fn score_packing_round_trip() {
+    for mg in VALUES {
+        for eg in VALUES {
+            let score = s(mg, eg);
+            assert_eq!((score.mg(), score.eg()), (mg, eg), "s({}, {})", mg, eg);
+        }
+    }
+    assert_eq!(s(0, 0), Score::ZERO);
+}
+
+#[test]
+fn score_arithmetic_borrows_between_halves() {
+    // a negative midgame half borrows from the endgame half of the packed value
+    let sum = s(-30, 50) + s(10, -80);
+    assert_eq!((sum.mg(), sum.eg()), (-20, -30));
+    let difference = s(5, -5) - s(10, 10);
+    assert_eq!((difference.mg(), difference.eg()), (-5, -15));
+    let negated = -s(-7, 12);
+    assert_eq!((negated.mg(), negated.eg()), (7, -12));
+    let scaled = s(-3, 4) * -5;
+    assert_eq!((scaled.mg(), scaled.eg()), (15, -20));
+
+    let mut total = Score::ZERO;
+    for (mg, eg) in [(-1, 1), (-1, -1), (3, -3), (-100, 0)] {
+        total += s(mg, eg);
+    }
+    assert_eq!((total.mg(), total.eg()), (-99, -3));
+    total -= s(-99, -3);
+    assert_eq!(total, Score::ZERO);
+}
+
+#[test]
+fn score_taper() {
+    let score = s(120, -60);
+    assert_eq!(score.taper(MAX_PHASE), 120);
+    assert_eq!(score.taper(0), -60);
+    assert_eq!(score.taper(MAX_PHASE / 2), 30);
+    assert_eq!(score.taper(MAX_PHASE / 4), (120 * 6 - 60 * 18) / 24);
+
+    assert_eq!(score.taper_scaled(MAX_PHASE / 2, SCALE_NORMAL), 30);
+    // only the endgame half is scaled
+    assert_eq!(score.taper_scaled(MAX_PHASE, 0), 120);
+    assert_eq!(score.taper_scaled(0, 0), 0);
+    assert_eq!(score.taper_scaled(0, SCALE_NORMAL / 2), -30);
+}
+
+// Walks every line `depth` plies deep carrying the bitboards and accumulator from move to
+// move, checking them against a rebuild from the position at every node
+fn check_incremental(
+    pos: &ChessPosition,
+    turn: Color,
+    board: &Bitboards,
+    acc: &Accumulator,
+    depth: u32,
+) {
+    let params = EvalParams::default();
+    assert_eq!(*board, Bitboards::new(pos), "{}", pos.fen());
+    assert_eq!(*acc, Accumulator::new(pos, &params), "{}", pos.fen());
+    assert_eq!(
+        evaluate_with(board, turn, acc, &params),
+        evaluate(pos, turn, &params)
+    );
+    if depth == 0 {
+        return;
+    }
+
+    for mv in generate_legal_moves(pos, turn, None, &[None; 2]) {
+        let mut child = pos.clone();
+        child.apply_move(mv).unwrap();
+        let delta = MoveDelta::new(pos, &mv);
+        check_incremental(
+            &child,
+            turn.other(),
+            &board.update(&delta),
+            &acc.update(&delta, &params),
+            depth - 1,
+        );
+    }
+}
+
+#[test]
+fn incremental_state_matches_a_rebuild() {
+    // castling both ways, captures, en passant and promotions with and without capture
+    let positions = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            3,
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            2,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            2,
+        ),
+        ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 3),
+    ];
+    for (fen, depth) in positions {
+        let pos = ChessPosition::from_fen(fen).unwrap();
+        let turn = if fen.contains(" w ") {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let board = Bitboards::new(&pos);
+        let acc = Accumulator::from_board(&board, &EvalParams::default());
+        check_incremental(&pos, turn, &board, &acc, depth);
+    }
+}
+
+// Evaluation terms of a position
+fn terms(fen: &str) -> Terms {
+    let board = Bitboards::new(&ChessPosition::from_fen(fen).unwrap());
+    Terms::new(&board, &EvalParams::default())
+}
+
+#[test]
+fn mobility_counts_safe_squares() {
+    let p = EvalParams::default();
+    // a centralized knight reaches eight squares, one in a corner only two
+    assert_eq!(
+        terms("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mobility,
+        [p.knight_mobility[8], Score::ZERO]
+    );
+    assert_eq!(
+        terms("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").mobility[WHITE],
+        p.knight_mobility[2]
+    );
+    // squares attacked by enemy pawns do not count
+    assert_eq!(
+        terms("4k3/8/6p1/8/3N4/8/8/4K3 w - - 0 1").mobility[WHITE],
+        p.knight_mobility[7]
+    );
+
+    // nor do squares of our own pieces, though the rook still sees through to them
+    assert_eq!(
+        terms("4k3/8/8/8/8/8/P7/RN2K3 w - - 0 1").mobility[WHITE],
+        p.rook_mobility[0] + p.knight_mobility[3]
+    );
+    // enemy pieces can be taken, and stop the slider behind them
+    assert_eq!(
+        terms("4k3/8/8/8/8/8/8/R2nK3 w - - 0 1").mobility[WHITE],
+        p.rook_mobility[10]
+    );
+    assert_eq!(
+        terms("3qk3/8/8/8/8/8/8/4K3 w - - 0 1").mobility[BLACK],
+        p.queen_mobility[17]
+    );
+}
+
+#[test]
+fn mobility_bishops() {
+    let p = EvalParams::default();
+    // a bishop on a long diagonal, then hemmed in by its own pawns on its colour
+    assert_eq!(
+        terms("4k3/8/8/8/8/8/8/B3K3 w - - 0 1").mobility[WHITE],
+        p.bishop_mobility[7]
+    );
+    assert_eq!(
+        terms("4k3/8/8/8/8/2P5/1P6/B3K3 w - - 0 1").mobility[WHITE],
+        p.bishop_mobility[0] + p.bad_bishop_penalty * 2
+    );
+    // the pair is worth a bonus on top of each bishop's mobility
+    assert_eq!(
+        terms("4k3/8/8/8/8/8/8/B3K2B w - - 0 1").mobility[WHITE],
+        p.bishop_mobility[7] * 2 + p.bishop_pair_bonus
+    );
+}
+
+#[test]
+fn mobility_trapped_rook() {
+    let p = EvalParams::default();
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let mobility = terms(start).mobility;
+    assert_eq!(mobility[WHITE], mobility[BLACK]);
+
+    // the rook is only trapped once the king cannot castle towards it
+    let rights = |castling: &str| terms(&start.replace("KQkq", castling)).mobility;
+    assert_eq!(rights("Kkq"), mobility);
+    assert_eq!(
+        rights("Qkq"),
+        [mobility[WHITE] + p.trapped_rook_penalty, mobility[BLACK]]
+    );
+    assert_eq!(
+        rights("-"),
+        [
+            mobility[WHITE] + p.trapped_rook_penalty,
+            mobility[BLACK] + p.trapped_rook_penalty
+        ]
+    );
+
+    // a king stepping aside keeps the rook behind it trapped, one castled is in the corner
+    let walked = terms("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1").mobility[WHITE];
+    let castled = terms("4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1").mobility[WHITE];
+    assert_eq!(walked, p.rook_mobility[1] + p.trapped_rook_penalty);
+    assert_eq!(castled, p.rook_mobility[5]);
+}
@@ -0,0 +1,67 @@
+use copperfish::bitboard::*;
+use copperfish::eval::{Score, Terms};
+use copperfish::params::EvalParams;
+use crabchess::prelude::ChessPosition;
+
+// King safety of both sides, with the given parameters
+fn king_safety(fen: &str, params: &EvalParams) -> [Score; 2] {
+    let board = Bitboards::new(&ChessPosition::from_fen(fen).unwrap());
+    Terms::new(&board, params).king_safety
+}
+
+#[test]
+fn king_shelter() {
+    let p = EvalParams::default();
+    // three pawns right in front of each king
+    let sheltered = king_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1", &p);
+    assert_eq!(sheltered, [p.shield_close_bonus * 3; 2]);
+
+    // without them the files around the white king are half open
+    let exposed = king_safety("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1", &p);
+    assert_eq!(
+        exposed[WHITE],
+        (p.king_semi_open_file_penalty + p.shield_missing_penalty) * 3
+    );
+    assert_eq!(exposed[BLACK], sheltered[BLACK]);
+    // and fully open once the enemy pawns are gone too
+    let open = king_safety("6k1/8/8/8/8/8/8/6K1 w - - 0 1", &p);
+    assert_eq!(
+        open[WHITE],
+        (p.king_open_file_penalty + p.shield_missing_penalty) * 3
+    );
+
+    // a pawn one step further away shields less
+    let advanced = king_safety("6k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1", &p);
+    assert_eq!(
+        advanced[WHITE],
+        p.shield_close_bonus * 2 + p.shield_far_bonus
+    );
+}
+
+#[test]
+fn king_danger_grows_with_attackers() {
+    let p = EvalParams::default();
+    let shelter = p.shield_close_bonus * 3;
+    // a lone knight hitting the king zone is not an attack yet
+    let knight = king_safety("k7/8/8/8/4n3/8/5PPP/6K1 w - - 0 1", &p)[WHITE];
+    assert_eq!(knight, shelter);
+
+    // a rook joins along the third rank, then the queen
+    let rook = king_safety("k7/8/8/8/4n3/r7/5PPP/6K1 w - - 0 1", &p)[WHITE];
+    let queen = king_safety("k7/8/8/8/4n2q/r7/5PPP/6K1 w - - 0 1", &p)[WHITE];
+    assert!(rook.mg() < knight.mg());
+    assert!(queen.mg() < rook.mg());
+    // only the midgame half is affected
+    assert_eq!((rook.eg(), queen.eg()), (shelter.eg(), shelter.eg()));
+
+    // heavier attacker weights make the same attack more dangerous
+    let heavier = EvalParams {
+        attacker_weights: p.attacker_weights.map(|weight| weight * 2),
+        ..EvalParams::default()
+    };
+    let heavier_rook = king_safety("k7/8/8/8/4n3/r7/5PPP/6K1 w - - 0 1", &heavier)[WHITE];
+    assert!(heavier_rook.mg() < rook.mg());
+
+    // attack units only ever add danger
+    assert!(p.king_danger.windows(2).all(|pair| pair[0] <= pair[1]));
+}
@@ -0,0 +1,59 @@
+use copperfish::kpk;
+
+// Square index of a square name, a8 = 0 and h1 = 63 as in the engine's bitboards
+fn sq(name: &str) -> usize {
+    let bytes = name.as_bytes();
+    let file = (bytes[0] - b'a') as usize;
+    let rank = (bytes[1] - b'1') as usize;
+    (7 - rank) * 8 + file
+}
+
+fn wins(strong_king: &str, pawn: &str, weak_king: &str, strong_to_move: bool) -> bool {
+    kpk::probe(sq(strong_king), sq(pawn), sq(weak_king), strong_to_move)
+}
+
+#[test]
+fn kpk_key_square() {
+    // the king two ranks ahead of its pawn wins whoever moves
+    assert!(wins("d6", "e4", "e8", true));
+    assert!(wins("d6", "e4", "e8", false));
+    assert!(wins("e6", "d4", "d8", true));
+    assert!(wins("e6", "d4", "d8", false));
+}
+
+#[test]
+fn kpk_opposition() {
+    // with the king in front of its pawn, the side to move loses the opposition
+    assert!(!wins("e5", "e4", "e7", true));
+    assert!(wins("e5", "e4", "e7", false));
+    assert!(!wins("d5", "d4", "d7", true));
+    assert!(wins("d5", "d4", "d7", false));
+}
+
+#[test]
+fn kpk_rule_of_the_square() {
+    assert!(wins("a1", "c5", "h8", true));
+    assert!(wins("a1", "c5", "h8", false));
+    assert!(wins("h1", "f5", "a8", false));
+    // the defending king steps into the square and blockades the pawn
+    assert!(!wins("a1", "b4", "e5", false));
+    assert!(!wins("h1", "g4", "d5", false));
+}
+
+#[test]
+fn kpk_rook_pawn() {
+    // a king in front of a rook pawn can never be driven out of the corner
+    assert!(!wins("g6", "h5", "h8", true));
+    assert!(!wins("g6", "h5", "h8", false));
+    assert!(!wins("b6", "a5", "a8", true));
+    assert!(!wins("b6", "a5", "a8", false));
+}
+
+#[test]
+fn kpk_promotion_and_stalemate() {
+    assert!(wins("a1", "g7", "a8", true));
+    // a pawn on the seventh shielded by its king, whose opponent is stalemated
+    assert!(!wins("f6", "f7", "f8", false));
+    // the defending king takes an undefended pawn
+    assert!(!wins("a1", "e4", "f4", false));
+}
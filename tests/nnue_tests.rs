@@ -0,0 +1,101 @@
+use copperfish::bitboard::Bitboards;
+use copperfish::eval::MoveDelta;
+use copperfish::nnue::Network;
+use copperfish::{moves, nnue};
+use crabchess::prelude::*;
+
+const HIDDEN: usize = 16;
+
+// A network of small pseudo-random weights, or with `output` as every output weight and
+// bias and every other weight at 255 when given
+fn network(name: &str, output: Option<i16>) -> Network {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut values: Vec<i16> = (0..769 * HIDDEN)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 58) as i16 - 32
+        })
+        .collect();
+    if output.is_some() {
+        values.fill(255);
+    }
+    values.extend(vec![output.unwrap_or(7); 2 * HIDDEN + 1]);
+
+    let path =
+        std::env::temp_dir().join(format!("copperfish-{}-{}.nnue", name, std::process::id()));
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    std::fs::write(&path, bytes).unwrap();
+    let network = Network::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    network.unwrap()
+}
+
+// Updates the accumulator through every line `depth` plies deep, checking it against a refresh
+fn check_updates(network: &Network, pos: &ChessPosition, turn: Color, depth: u32) {
+    let acc = network.refresh(&Bitboards::new(pos));
+    if depth == 0 {
+        return;
+    }
+
+    for mv in moves::generate_legal_moves(pos, turn, None, &[None; 2]) {
+        let mut child = pos.clone();
+        child.apply_move(mv).unwrap();
+        let mut child_acc = nnue::NnueAccumulator::empty(HIDDEN);
+        network.update(&acc, &mut child_acc, &MoveDelta::new(pos, &mv));
+        assert_eq!(
+            child_acc,
+            network.refresh(&Bitboards::new(&child)),
+            "{:?}",
+            mv
+        );
+        check_updates(network, &child, turn.other(), depth - 1);
+    }
+    // the parent accumulator is left as it was, ready for the next move
+    assert_eq!(acc, network.refresh(&Bitboards::new(pos)));
+}
+
+#[test]
+fn nnue_incremental_updates() {
+    let network = network("updates", None);
+    // castling both ways, captures, en passant and promotions with and without capture
+    let positions = [
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            2,
+        ),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            2,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            1,
+        ),
+        ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 2),
+    ];
+    for (fen, depth) in positions {
+        let pos = ChessPosition::from_fen(fen).unwrap();
+        let turn = if fen.contains(" w ") {
+            Color::White
+        } else {
+            Color::Black
+        };
+        check_updates(&network, &pos, turn, depth);
+    }
+}
+
+#[test]
+fn nnue_large_output_does_not_overflow() {
+    // every hidden value clipped to 255 and every output weight at the i16 maximum
+    let network = network("overflow", Some(i16::MAX));
+    let acc = network.refresh(&Bitboards::new(&ChessPosition::default()));
+    let sum = 255 * i16::MAX as i64 * 2 * HIDDEN as i64 + i16::MAX as i64;
+    let expected = (sum * 400 / (255 * 64)) as i32;
+    assert_eq!(network.evaluate(&acc, Color::White), expected);
+    assert_eq!(network.evaluate(&acc, Color::Black), -expected);
+}
@@ -0,0 +1,33 @@
+use copperfish::eval::s;
+use copperfish::params::EvalParams;
+
+#[test]
+fn params_toml_round_trip() {
+    let defaults = EvalParams::default();
+    assert_eq!(EvalParams::from_toml(&defaults.to_toml()), Ok(defaults));
+
+    let mut params = EvalParams::default();
+    params.piece_values[1] = s(301, 288);
+    params.rook_pst[63] = s(-7, 12);
+    params.passed_own_king_distance = 9;
+    params.king_danger[99] = 1234;
+    let read = EvalParams::from_toml(&params.to_toml()).unwrap();
+    assert_eq!(read, params);
+}
+
+#[test]
+fn params_partial_and_invalid_toml() {
+    let partial = "bishop_pair_bonus = [40, 60]\nzone_hit_units = 7\n";
+    let params = EvalParams::from_toml(partial).unwrap();
+    assert_eq!(params.bishop_pair_bonus, s(40, 60));
+    assert_eq!(params.zone_hit_units, 7);
+    assert_eq!(params.knight_pst, EvalParams::default().knight_pst);
+    assert_eq!(EvalParams::from_toml(""), Ok(EvalParams::default()));
+
+    assert_eq!(
+        EvalParams::from_toml("bishop_pair_bonus = [40, 60]\nqueen_pair_bonus = 5\n"),
+        Err("unknown parameter queen_pair_bonus".to_string())
+    );
+    assert!(EvalParams::from_toml("zone_hit_units = [1, 2]").is_err());
+    assert!(EvalParams::from_toml("piece_values = [[100, 100]]").is_err());
+}
@@ -0,0 +1,177 @@
+use copperfish::bitboard::*;
+use copperfish::eval::{Score, s};
+use copperfish::params::EvalParams;
+use copperfish::pawns::{self, PawnEntry};
+use crabchess::prelude::ChessPosition;
+
+fn board(fen: &str) -> Bitboards {
+    Bitboards::new(&ChessPosition::from_fen(fen).unwrap())
+}
+
+// Pawn structure entry of a position
+fn pawn_entry(fen: &str) -> PawnEntry {
+    let board = board(fen);
+    pawns::probe(
+        [board.pieces[WHITE][PAWN], board.pieces[BLACK][PAWN]],
+        &EvalParams::default(),
+    )
+}
+
+// King and passed pawn terms, with `has_pieces` telling which sides have more than pawns
+fn passed_terms(fen: &str, has_pieces: [bool; 2]) -> [Score; 2] {
+    let board = board(fen);
+    pawns::passed_pawns(
+        &pawn_entry(fen),
+        [board.king(WHITE), board.king(BLACK)],
+        has_pieces,
+        &EvalParams::default(),
+    )
+}
+
+fn sq(name: &str) -> usize {
+    let bytes = name.as_bytes();
+    square_at((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize)
+}
+
+#[test]
+fn pawns_passed_and_isolated() {
+    let p = EvalParams::default();
+    let entry = pawn_entry("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed, [1 << sq("d5"), 0]);
+    assert_eq!(
+        entry.scores,
+        [
+            p.passed_pawn_bonus[4] + p.isolated_pawn_penalty,
+            Score::ZERO
+        ]
+    );
+    assert_eq!(entry.pawn_files, [1 << 3, 0]);
+
+    // an enemy pawn on a neighbouring file ahead stops it, and the other way round
+    let entry = pawn_entry("4k3/8/4p3/3P4/8/8/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed, [0, 0]);
+    assert_eq!(entry.scores, [p.isolated_pawn_penalty; 2]);
+    // once they are past each other both are passed, ranks counted from each side
+    let entry = pawn_entry("4k3/8/8/3P4/4p3/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        entry.scores[BLACK],
+        p.passed_pawn_bonus[4] + p.isolated_pawn_penalty
+    );
+    let entry = pawn_entry("4k3/8/8/3P4/4p3/8/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed, [1 << sq("d5"), 1 << sq("e4")]);
+}
+
+#[test]
+fn pawns_doubled() {
+    let p = EvalParams::default();
+    // both doubled pawns are isolated, only the front one can be passed
+    let entry = pawn_entry("4k3/8/8/8/3P4/3P4/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed[WHITE], 1 << sq("d4"));
+    assert_eq!(
+        entry.scores[WHITE],
+        p.passed_pawn_bonus[3] + p.doubled_pawn_penalty + p.isolated_pawn_penalty * 2
+    );
+
+    let entry = pawn_entry("4k3/3p4/8/8/3P4/3P4/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed, [0, 0]);
+    assert_eq!(
+        entry.scores[WHITE],
+        p.doubled_pawn_penalty + p.isolated_pawn_penalty * 2
+    );
+}
+
+#[test]
+fn pawns_connected_and_backward() {
+    let p = EvalParams::default();
+    // side by side and defended pawns are connected, scored by their rank: b2 is defended
+    // by c3, which is not backward as no enemy pawn controls its stop square
+    let entry = pawn_entry("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        entry.scores[WHITE],
+        (p.passed_pawn_bonus[3] + p.connected_pawn_bonus[3]) * 2
+    );
+    let entry = pawn_entry("4k3/8/8/8/8/2p5/1p6/4K3 w - - 0 1");
+    assert_eq!(
+        entry.scores[BLACK],
+        p.passed_pawn_bonus[6] + p.passed_pawn_bonus[5] + p.connected_pawn_bonus[6]
+    );
+
+    // d3 has no neighbour left behind it and e5 controls d4: backward. c4 is defended by it.
+    let entry = pawn_entry("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+    assert_eq!(entry.passed[WHITE], 1 << sq("c4"));
+    assert_eq!(
+        entry.scores[WHITE],
+        p.backward_pawn_penalty + p.passed_pawn_bonus[3] + p.connected_pawn_bonus[3]
+    );
+    // without the enemy pawn it can safely catch up
+    let entry = pawn_entry("4k3/8/8/8/2P5/3P4/8/4K3 w - - 0 1");
+    assert_eq!(
+        entry.scores[WHITE],
+        p.passed_pawn_bonus[2] + p.passed_pawn_bonus[3] + p.connected_pawn_bonus[3]
+    );
+}
+
+#[test]
+fn passed_pawn_king_proximity() {
+    let p = EvalParams::default();
+    // kings 4 and 6 squares from e7, in front of the pawn, weighted by its rank
+    let terms = passed_terms("k7/8/4P3/8/8/8/8/4K3 w - - 0 1", [true, true]);
+    let proximity = p.passed_enemy_king_distance * 4 - p.passed_own_king_distance * 6;
+    assert_eq!(terms, [s(0, proximity * 3), Score::ZERO]);
+
+    let near = passed_terms("5k2/8/4P3/8/8/8/8/4K3 w - - 0 1", [true, true]);
+    let escorted = passed_terms("k7/8/4PK2/8/8/8/8/8 w - - 0 1", [true, true]);
+    assert!(near[WHITE].eg() < terms[WHITE].eg());
+    assert!(escorted[WHITE].eg() > terms[WHITE].eg());
+    // not weighted below the fourth rank
+    let terms = passed_terms("k7/8/8/8/8/4P3/8/4K3 w - - 0 1", [true, true]);
+    assert_eq!(terms, [Score::ZERO; 2]);
+}
+
+#[test]
+fn passed_pawn_unstoppable() {
+    let p = EvalParams::default();
+    // the bonus alone, the other passed pawn terms taken out
+    let unstoppable = |fen: &str, has_pieces| {
+        passed_terms(fen, has_pieces)[WHITE] - passed_terms(fen, [true, true])[WHITE]
+    };
+    // the king on f8 catches the pawn on a4, the one on g8 cannot
+    assert_eq!(
+        unstoppable("5k2/8/8/8/P7/8/8/4K3 w - - 0 1", [true, false]),
+        Score::ZERO
+    );
+    assert_eq!(
+        unstoppable("6k1/8/8/8/P7/8/8/4K3 w - - 0 1", [true, false]),
+        p.unstoppable_passer_bonus
+    );
+    // only against a lone king
+    assert_eq!(
+        unstoppable("6k1/8/8/8/P7/8/8/4K3 w - - 0 1", [true, true]),
+        Score::ZERO
+    );
+
+    // from its starting rank the pawn pushes two squares, as fast as from the third
+    assert_eq!(
+        unstoppable("7k/8/8/8/8/8/P7/4K3 w - - 0 1", [true, false]),
+        p.unstoppable_passer_bonus
+    );
+    assert_eq!(
+        unstoppable("6k1/8/8/8/8/8/P7/4K3 w - - 0 1", [true, false]),
+        Score::ZERO
+    );
+    // its own king in the way costs it a move
+    assert_eq!(
+        unstoppable("6k1/K7/8/8/P7/8/8/8 w - - 0 1", [true, false]),
+        Score::ZERO
+    );
+
+    // and for Black, queening on the first rank
+    let black = |fen: &str| {
+        passed_terms(fen, [false, true])[BLACK] - passed_terms(fen, [true, true])[BLACK]
+    };
+    assert_eq!(
+        black("4k3/8/8/p7/8/8/8/6K1 b - - 0 1"),
+        p.unstoppable_passer_bonus
+    );
+    assert_eq!(black("4k3/8/8/p7/8/8/8/5K2 b - - 0 1"), Score::ZERO);
+}
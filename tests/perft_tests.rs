@@ -1,4 +1,5 @@
 use chess::*;
+use copperfish::moves;
 use std::str::FromStr;
 
 pub fn perft(game: &Game, depth: u32) -> u64 {
@@ -76,3 +77,45 @@ fn perft_position_6() {
     assert_eq!(perft(&game, 2), 2079);
     assert_eq!(perft(&game, 3), 89890);
 }
+
+// Leaf count of the engine's own generator
+fn engine_perft(pos: &crabchess::prelude::ChessPosition, white: bool, depth: u32) -> u64 {
+    use crabchess::prelude::Color as Side;
+    if depth == 0 {
+        return 1;
+    }
+
+    let turn = if white { Side::White } else { Side::Black };
+    let mut nodes = 0;
+    for mv in moves::generate_legal_moves(pos, turn, None, &[None; 2]) {
+        let mut child = pos.clone();
+        child.apply_move(mv).unwrap();
+        nodes += engine_perft(&child, !white, depth - 1);
+    }
+    nodes
+}
+
+fn engine_perft_fen(fen: &str, depth: u32) -> u64 {
+    let pos = crabchess::prelude::ChessPosition::from_fen(fen).expect("Valid FEN");
+    engine_perft(&pos, fen.split_whitespace().nth(1) == Some("w"), depth)
+}
+
+#[test]
+fn engine_perft_en_passant() {
+    // en passant captures are counted once, not as a move and again as a threat
+    assert_eq!(
+        engine_perft_fen(
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            2
+        ),
+        707
+    );
+    assert_eq!(
+        engine_perft_fen("8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3", 2),
+        72
+    );
+    assert_eq!(
+        engine_perft_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+        2812
+    );
+}
@@ -0,0 +1,172 @@
+use copperfish::pgn::{Game, PgnMove, Reader, Token, Tokenizer};
+use std::time::Duration;
+
+fn read(text: &str) -> Vec<Result<Game, String>> {
+    Reader::new(text.as_bytes()).collect()
+}
+
+#[test]
+fn pgn_tokens() {
+    let text = "[Event \"Test \\\"quoted\\\"\"]\n\n1.e4 e5!? 2. Nf3 $1 {a comment\non two lines} (2. f4) ; rest of line\n% escaped line\n0-0 1/2-1/2";
+    let tokens: Vec<Token> = Tokenizer::new(text.as_bytes())
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Tag("Event".into(), "Test \"quoted\"".into()),
+            Token::MoveNumber(1),
+            Token::San("e4".into()),
+            Token::San("e5".into()),
+            Token::Nag(5),
+            Token::MoveNumber(2),
+            Token::San("Nf3".into()),
+            Token::Nag(1),
+            Token::Comment("a comment on two lines".into()),
+            Token::VariationStart,
+            Token::MoveNumber(2),
+            Token::San("f4".into()),
+            Token::VariationEnd,
+            Token::Comment("rest of line".into()),
+            Token::San("O-O".into()),
+            Token::Result("1/2-1/2".into()),
+        ]
+    );
+}
+
+#[test]
+fn pgn_games_and_variations() {
+    let text = "[White \"A\"]\n[Result \"1-0\"]\n\n{opening} 1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 $14 1-0\n\n\
+                [White \"B\"]\n\n1. d4 *\n";
+    let games: Vec<Game> = read(text).into_iter().map(Result::unwrap).collect();
+    assert_eq!(games.len(), 2);
+
+    let game = &games[0];
+    assert_eq!(game.tag("White"), Some("A"));
+    assert_eq!(game.score(), Some(1.0));
+    assert_eq!(game.comment.as_deref(), Some("opening"));
+    assert_eq!(game.sans().collect::<Vec<_>>(), ["e4", "e5", "Nf3"]);
+    assert_eq!(game.moves[2].nags, [14]);
+    let variation = &game.moves[1].variations[0];
+    assert_eq!(variation[0].san, "c5");
+    assert_eq!(variation[1].variations[0][0].san, "c3");
+
+    assert_eq!(games[1].tag("White"), Some("B"));
+    assert_eq!(games[1].score(), None);
+}
+
+#[test]
+fn pgn_recovers_from_malformed_games() {
+    let text = "[Event \"1\"]\n1. e4 ) e5 1-0\n\n[Event \"2\"]\n1. d4 <> d5 *\n\n[Event \"3\"]\n1. c4 0-1\n";
+    let games = read(text);
+    assert_eq!(games.len(), 3);
+    assert!(games[0].is_err());
+    assert!(games[1].is_err());
+    let game = games[2].as_ref().unwrap();
+    assert_eq!(game.tag("Event"), Some("3"));
+    assert_eq!(game.sans().collect::<Vec<_>>(), ["c4"]);
+
+    // a game left without a result ends at the tags of the next one
+    let games = read("[Event \"1\"]\n1. e4 e5\n[Event \"2\"]\n1. d4 1-0\n");
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].as_ref().unwrap().result, "*");
+    assert_eq!(games[1].as_ref().unwrap().tag("Event"), Some("2"));
+
+    assert!(read("1. e4 {never closed").iter().all(Result::is_err));
+}
+
+#[test]
+fn pgn_clock_comments() {
+    let mut mv = PgnMove::new("e4");
+    mv.set_clock("clk", Duration::from_secs(3723));
+    assert_eq!(mv.comment.as_deref(), Some("[%clk 1:02:03]"));
+
+    mv.comment = Some(String::from("[%clk 0:05:00] best by test"));
+    mv.set_clock("clk", Duration::from_secs(59));
+    assert_eq!(mv.comment.as_deref(), Some("[%clk 0:00:59] best by test"));
+    // other clock commands are kept apart, with tenths of a second
+    mv.set_clock("emt", Duration::from_millis(2540));
+    assert_eq!(
+        mv.comment.as_deref(),
+        Some("[%emt 0:00:02.5] [%clk 0:00:59] best by test")
+    );
+    mv.set_clock("emt", Duration::from_millis(99));
+    assert_eq!(
+        mv.comment.as_deref(),
+        Some("[%emt 0:00:00] [%clk 0:00:59] best by test")
+    );
+}
+
+// Fails once its text is read, as a file on a broken disk or connection would
+struct FailingReader<'a>(&'a [u8]);
+
+impl std::io::Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(std::io::Error::other("device error"));
+        }
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn pgn_encoding_and_read_errors() {
+    // Latin-1 names and comments read with replacement characters, not as the end of the file
+    let text = b"[White \"M\xfcller\"]\n\n1. e4 {d\xe9but} e5 1-0\n\n[White \"B\"]\n\n1. d4 0-1\n";
+    let games: Vec<_> = Reader::new(&text[..]).collect();
+    assert_eq!(games.len(), 2);
+    let first = games[0].as_ref().unwrap();
+    assert_eq!(first.tag("White"), Some("M\u{fffd}ller"));
+    assert_eq!(first.moves[0].comment.as_deref(), Some("d\u{fffd}but"));
+    assert_eq!(games[1].as_ref().unwrap().result, "0-1");
+
+    // a read error is reported once, then reading stops
+    let reader = std::io::BufReader::new(FailingReader(b"1. e4 e5 1-0\n\n1. d4"));
+    let tokens: Vec<_> = Tokenizer::new(reader).collect();
+    assert_eq!(tokens.iter().filter(|token| token.is_err()).count(), 1);
+    assert_eq!(
+        tokens.last(),
+        Some(&Err(String::from("line 3: device error")))
+    );
+
+    let reader = std::io::BufReader::new(FailingReader(b"1. e4 e5 1-0\n\n1. d4 {cut"));
+    let games: Vec<_> = Reader::new(reader).collect();
+    assert_eq!(games.len(), 2);
+    assert!(games[0].is_ok());
+    assert_eq!(games[1], Err(String::from("line 3: device error")));
+}
+
+#[test]
+fn pgn_write_round_trip() {
+    let mut game = Game::default();
+    game.set_tag("White", "Copperfish \"dev\"");
+    game.set_tag(
+        "FEN",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+    );
+    game.result = String::from("0-1");
+    let mut moves: Vec<PgnMove> = ["c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6"]
+        .iter()
+        .cycle()
+        .take(60)
+        .map(|san| PgnMove::new(san))
+        .collect();
+    moves[0].set_clock("clk", Duration::from_secs(180));
+    moves[1].nags.push(3);
+    moves[1]
+        .variations
+        .push(vec![PgnMove::new("c3"), PgnMove::new("Nf6")]);
+    game.moves = moves;
+
+    let pgn = game.to_pgn();
+    assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n"));
+    assert!(pgn.contains("[White \"Copperfish \\\"dev\\\"\"]"));
+    assert!(pgn.contains("1... c5 {[%clk 0:03:00]} 2. Nf3 $3 (2. c3 Nf6) 2... d6"));
+    assert!(pgn.lines().all(|line| line.len() < 80));
+    assert!(pgn.trim_end().ends_with("0-1"));
+
+    let read_back = read(&pgn).remove(0).unwrap();
+    assert_eq!(read_back.moves, game.moves);
+    assert_eq!(read_back.result, game.result);
+    assert_eq!(read_back.fen(), game.fen());
+}
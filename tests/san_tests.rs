@@ -0,0 +1,114 @@
+use copperfish::san::{parse_san, san};
+use copperfish::uci::{UciFormat, move_from_uci};
+use crabchess::prelude::*;
+
+fn position(fen: &str) -> (ChessPosition, Color) {
+    let turn = if fen.contains(" w ") {
+        Color::White
+    } else {
+        Color::Black
+    };
+    (ChessPosition::from_fen(fen).unwrap(), turn)
+}
+
+// SAN of a move given in UCI notation
+fn to_san(fen: &str, uci: &str) -> String {
+    let (pos, turn) = position(fen);
+    san(&pos, turn, &move_from_uci(&pos, turn, uci).unwrap())
+}
+
+// UCI notation of a move given in SAN
+fn from_san(fen: &str, text: &str) -> Result<String, String> {
+    let (pos, turn) = position(fen);
+    parse_san(&pos, turn, text).map(|mv| mv.uci())
+}
+
+#[test]
+fn san_disambiguation() {
+    // by file, by rank, and by square when neither is enough
+    let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+    assert_eq!(to_san(knights, "b1d2"), "Nbd2");
+    assert_eq!(to_san(knights, "f1d2"), "Nfd2");
+    assert_eq!(to_san(knights, "b1c3"), "Nc3");
+    let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+    assert_eq!(to_san(rooks, "a1a3"), "R1a3");
+    assert_eq!(to_san(rooks, "a5a3"), "R5a3");
+    let queens = "1k6/8/8/8/7Q/8/8/4Q1KQ w - - 0 1";
+    assert_eq!(to_san(queens, "h1e4"), "Qh1e4");
+    assert_eq!(to_san(queens, "h4e4"), "Q4e4");
+    assert_eq!(to_san(queens, "e1e4"), "Qee4");
+
+    for (fen, text, uci) in [
+        (knights, "Nbd2", "b1d2"),
+        (knights, "Nf1d2", "f1d2"),
+        (rooks, "R5a3", "a5a3"),
+        (rooks, "Ra1a3", "a1a3"),
+        (queens, "Qh1e4", "h1e4"),
+        (queens, "Qh1xe4", "h1e4"),
+        (queens, "Q4e4", "h4e4"),
+    ] {
+        assert_eq!(from_san(fen, text).as_deref(), Ok(uci), "{}", text);
+    }
+}
+
+#[test]
+fn san_promotions_and_castling() {
+    let fen = "3r4/4P1k1/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(to_san(fen, "e7e8q"), "e8=Q");
+    assert_eq!(to_san(fen, "e7d8n"), "exd8=N");
+    assert_eq!(from_san(fen, "e8=Q").as_deref(), Ok("e7e8q"));
+    assert_eq!(from_san(fen, "e8Q").as_deref(), Ok("e7e8q"));
+    assert_eq!(from_san(fen, "exd8=R").as_deref(), Ok("e7d8r"));
+    assert_eq!(from_san(fen, "exd8B").as_deref(), Ok("e7d8b"));
+    assert!(from_san(fen, "e8").is_err());
+
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(to_san(fen, "e1g1"), "O-O");
+    assert_eq!(to_san(fen, "e1c1"), "O-O-O");
+    assert_eq!(from_san(fen, "O-O").as_deref(), Ok("e1g1"));
+    assert_eq!(from_san(fen, "0-0-0").as_deref(), Ok("e1c1"));
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+    assert_eq!(from_san(fen, "0-0").as_deref(), Ok("e8g8"));
+    assert_eq!(from_san(fen, "O-O-O").as_deref(), Ok("e8c8"));
+
+    let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2";
+    assert_eq!(to_san(fen, "e5d6"), "exd6");
+    assert_eq!(from_san(fen, "exd6").as_deref(), Ok("e5d6"));
+}
+
+#[test]
+fn san_check_and_mate() {
+    assert_eq!(to_san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+    assert_eq!(to_san("6k1/8/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8+");
+    assert_eq!(to_san("6k1/8/8/8/8/8/8/R5K1 w - - 0 1", "a1a7"), "Ra7");
+    // suffixes and annotations are ignored when reading
+    let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+    assert_eq!(from_san(fen, "Ra8#").as_deref(), Ok("a1a8"));
+    assert_eq!(from_san(fen, "Ra8+!?").as_deref(), Ok("a1a8"));
+}
+
+#[test]
+fn san_errors() {
+    let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+    assert_eq!(
+        from_san(knights, "Nd2"),
+        Err(String::from("ambiguous move Nd2"))
+    );
+    assert_eq!(
+        from_san(knights, "Nd3"),
+        Err(String::from("illegal move Nd3"))
+    );
+    assert_eq!(
+        from_san(knights, "Ke3x"),
+        Err(String::from("illegal move Ke3x"))
+    );
+    assert_eq!(
+        from_san(knights, "Nz9"),
+        Err(String::from("unreadable move Nz9"))
+    );
+    assert_eq!(from_san(knights, ""), Err(String::from("unreadable move ")));
+    // a pinned piece has a single legal move to the square
+    let pinned = "3rk3/8/8/8/8/8/1N1N4/3K4 w - - 0 1";
+    assert_eq!(from_san(pinned, "Nc4").as_deref(), Ok("b2c4"));
+    assert_eq!(to_san(pinned, "b2c4"), "Nc4");
+}
@@ -0,0 +1,191 @@
+use copperfish::eval::MAX_PHASE;
+use copperfish::moves::{halfmove_clock, is_irreversible};
+use copperfish::params::EvalParams;
+use copperfish::search::*;
+use copperfish::uci::move_from_uci;
+use copperfish::zobrist::Zobrist;
+use crabchess::prelude::*;
+use std::sync::{Arc, atomic::AtomicBool};
+
+/// A game replayed move by move, recording its positions in a `History`
+struct Replay {
+    zob: Zobrist,
+    position: ChessPosition,
+    turn: Color,
+    history: History,
+}
+
+impl Replay {
+    fn new(fen: &str) -> Self {
+        let zob = Zobrist::with_seed(1);
+        let position = ChessPosition::from_fen(fen).unwrap();
+        let turn = if fen.contains(" w ") {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let history = History::new(
+            zob.hash_position(&position, turn),
+            halfmove_clock(&position),
+        );
+        Replay {
+            zob,
+            position,
+            turn,
+            history,
+        }
+    }
+
+    fn play(&mut self, moves: &str) {
+        for text in moves.split_whitespace() {
+            let mv = move_from_uci(&self.position, self.turn, text).unwrap();
+            let irreversible = is_irreversible(&self.position, &mv);
+            self.position.apply_move(mv).unwrap();
+            self.turn = self.turn.other();
+            self.history.push(
+                self.zob.hash_position(&self.position, self.turn),
+                irreversible,
+            );
+        }
+    }
+}
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KNIGHTS_OUT_AND_BACK: &str = "g1f3 g8f6 f3g1 f6g8";
+
+// Score of a depth 3 search from White's point of view
+fn search_score(fen: &str) -> i32 {
+    let Replay {
+        zob,
+        position,
+        turn,
+        mut history,
+    } = Replay::new(fen);
+    let params = EvalParams::default();
+    let mut tt = TranspositionTable::new();
+    let stop_flag = Arc::new(AtomicBool::new(false));
+    let mut searcher = Searcher::new(
+        &mut tt,
+        &zob,
+        &params,
+        None,
+        &mut history,
+        Contempt::new(),
+        &stop_flag,
+    );
+    let mut score = 0;
+    for depth in 1..=3 {
+        score = searcher
+            .aspiration_search(&position, turn, score, depth, 25)
+            .unwrap()
+            .1;
+    }
+    score
+}
+
+#[test]
+fn history_twofold_repetition_in_search_path() {
+    let mut replay = Replay::new(START);
+    replay.play("e2e4 e7e5");
+    replay.history.set_root();
+    replay.play(KNIGHTS_OUT_AND_BACK);
+    // back to the root position once, which is enough below the root
+    assert!(replay.history.is_repetition());
+
+    replay.history.pop();
+    assert!(!replay.history.is_repetition());
+}
+
+#[test]
+fn history_threefold_repetition_in_game() {
+    let mut replay = Replay::new(START);
+    replay.play("e2e4 e7e5");
+    replay.play(KNIGHTS_OUT_AND_BACK);
+    // a position of the game seen twice is not a draw yet
+    replay.history.set_root();
+    assert!(!replay.history.is_repetition());
+
+    replay.play(KNIGHTS_OUT_AND_BACK);
+    replay.history.set_root();
+    assert!(replay.history.is_repetition());
+}
+
+#[test]
+fn history_repetition_needs_the_same_castling_rights() {
+    let mut replay = Replay::new(START);
+    replay.play("e2e4 e7e5");
+    replay.history.set_root();
+    // the kings walk out and back, but can no longer castle
+    replay.play("e1e2 e8e7 e2e1 e7e8");
+    assert!(!replay.history.is_repetition());
+
+    replay.play("e1e2 e8e7 e2e1 e7e8");
+    assert!(replay.history.is_repetition());
+}
+
+#[test]
+fn history_halfmove_clock() {
+    let mut replay = Replay::new("4k3/8/8/8/8/8/4P3/R3K3 w - - 37 60");
+    assert_eq!(replay.history.halfmove_clock(), 37);
+    replay.play("a1a7 e8d8");
+    assert_eq!(replay.history.halfmove_clock(), 39);
+    // a pawn move resets it
+    replay.play("e2e4");
+    assert_eq!(replay.history.halfmove_clock(), 0);
+    replay.play("d8e8 a7a8");
+    assert_eq!(replay.history.halfmove_clock(), 2);
+
+    // and so does a capture
+    let mut replay = Replay::new("4k3/8/8/8/8/8/r7/R3K3 w - - 12 40");
+    replay.play("a1a2");
+    assert_eq!(replay.history.halfmove_clock(), 0);
+}
+
+#[test]
+fn search_scores_the_fifty_move_rule() {
+    // a rook up, but every move reaches the hundredth ply without a capture or pawn move
+    let fen = "8/8/8/4k3/8/8/3K4/7R w - - 99 120";
+    assert_eq!(search_score(fen), 0);
+    assert!(search_score(&fen.replace(" 99 ", " 0 ")) > 300);
+}
+
+#[test]
+fn contempt_draw_score_sign() {
+    let contempt = Contempt {
+        value: 40,
+        scale_by_phase: false,
+    };
+    // a draw is worse than even for the engine, scores being from White's point of view
+    assert_eq!(contempt.draw_score(MAX_PHASE, Color::White), -40);
+    assert_eq!(contempt.draw_score(MAX_PHASE, Color::Black), 40);
+
+    // a negative contempt makes the engine look for draws
+    let drawish = Contempt {
+        value: -40,
+        ..contempt
+    };
+    assert_eq!(drawish.draw_score(MAX_PHASE, Color::White), 40);
+    assert_eq!(drawish.draw_score(MAX_PHASE, Color::Black), -40);
+    assert_eq!(Contempt::new().draw_score(MAX_PHASE, Color::White), 0);
+}
+
+#[test]
+fn contempt_scaling_with_phase() {
+    let scaled = Contempt {
+        value: 40,
+        scale_by_phase: true,
+    };
+    assert_eq!(scaled.draw_score(MAX_PHASE, Color::White), -40);
+    assert_eq!(scaled.draw_score(MAX_PHASE / 2, Color::White), -20);
+    assert_eq!(scaled.draw_score(MAX_PHASE / 2, Color::Black), 20);
+    assert_eq!(scaled.draw_score(0, Color::Black), 0);
+    // promotions can push the phase past its opening value
+    assert_eq!(scaled.draw_score(MAX_PHASE + 6, Color::White), -40);
+
+    let unscaled = Contempt {
+        scale_by_phase: false,
+        ..scaled
+    };
+    assert_eq!(unscaled.draw_score(0, Color::White), -40);
+    assert_eq!(unscaled.draw_score(MAX_PHASE / 2, Color::Black), 40);
+}
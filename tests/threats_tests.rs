@@ -0,0 +1,69 @@
+use copperfish::bitboard::*;
+use copperfish::eval::{Score, Terms};
+use copperfish::params::EvalParams;
+use crabchess::prelude::ChessPosition;
+
+// Threats made by each side
+fn threats(fen: &str) -> [Score; 2] {
+    let board = Bitboards::new(&ChessPosition::from_fen(fen).unwrap());
+    Terms::new(&board, &EvalParams::default()).threats
+}
+
+#[test]
+fn threat_by_pawn() {
+    let p = EvalParams::default();
+    // the e4 pawn attacks the knight on d5, defended by the c6 pawn
+    assert_eq!(
+        threats("k7/8/2p5/3n4/4P3/8/8/7K w - - 0 1"),
+        [p.threat_by_safe_pawn, Score::ZERO]
+    );
+    // once the rook attacks the pawn it is no threat anymore
+    assert_eq!(
+        threats("k3r3/8/2p5/3n4/4P3/8/8/7K w - - 0 1")[WHITE],
+        Score::ZERO
+    );
+    // a pawn push to e5 would attack the knight on d6
+    assert_eq!(
+        threats("k7/2p5/3n4/8/4P3/8/8/7K w - - 0 1")[WHITE],
+        p.threat_by_pawn_push
+    );
+}
+
+#[test]
+fn threat_by_minor() {
+    let p = EvalParams::default();
+    // the knight on c3 attacks the rook or queen on d5, defended by the e6 pawn
+    assert_eq!(
+        threats("k7/8/4p3/3r4/8/2N5/8/7K w - - 0 1"),
+        [p.threat_by_minor[ROOK], Score::ZERO]
+    );
+    assert_eq!(
+        threats("k7/8/4p3/3q4/8/2N5/8/K7 w - - 0 1"),
+        [p.threat_by_minor[QUEEN], Score::ZERO]
+    );
+    // and so does a bishop
+    assert_eq!(
+        threats("k7/8/4p3/3r4/8/1B6/8/7K w - - 0 1"),
+        [p.threat_by_minor[ROOK], Score::ZERO]
+    );
+}
+
+#[test]
+fn threat_hanging_piece() {
+    let p = EvalParams::default();
+    // the knight on d5 is attacked by the rook and defended by nothing
+    assert_eq!(
+        threats("k7/8/8/3n4/8/8/8/3R3K w - - 0 1"),
+        [p.threat_by_rook[KNIGHT] + p.hanging_penalty, Score::ZERO]
+    );
+    // a pawn defending it leaves the rook nothing to win
+    assert_eq!(
+        threats("k7/8/2p5/3n4/8/8/8/3R3K w - - 0 1"),
+        [Score::ZERO; 2]
+    );
+    // attacked twice and defended once by a piece, it still hangs
+    assert_eq!(
+        threats("k2r4/8/8/3n4/8/2N5/8/3R3K w - - 0 1")[WHITE],
+        p.threat_by_minor[KNIGHT] + p.threat_by_rook[KNIGHT] + p.hanging_penalty
+    );
+}
@@ -0,0 +1,54 @@
+use copperfish::bitboard::*;
+use copperfish::eval::s;
+use copperfish::params::EvalParams;
+use copperfish::tune::parse_entry;
+use crabchess::prelude::Color;
+
+#[test]
+fn tune_dataset_lines() {
+    let entry =
+        parse_entry("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1 [1.0]").unwrap();
+    assert_eq!(entry.turn, Color::Black);
+    assert_eq!(entry.result, 1.0);
+    assert_eq!(entry.board.pieces[WHITE][PAWN].count_ones(), 8);
+    assert_ne!(entry.board.pieces[WHITE][PAWN] & (1 << square_at(4, 3)), 0);
+
+    // clocks are optional and the result can be written as a game result
+    let entry = parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - - 1/2-1/2").unwrap();
+    assert_eq!((entry.turn, entry.result), (Color::White, 0.5));
+    assert_eq!(
+        parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - - 12 40 \"0-1\";")
+            .unwrap()
+            .result,
+        0.0
+    );
+
+    // datagen lines
+    let entry = parse_entry("8/8/4k3/8/8/4K3/4P3/8 b - - 0 1 | -35 | 1.0").unwrap();
+    assert_eq!((entry.turn, entry.result), (Color::Black, 1.0));
+
+    assert!(parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - -").is_none());
+    assert!(parse_entry("8/8/4k3/8 w - - [0.5]").is_none());
+    assert!(parse_entry("not a position [0.5]").is_none());
+}
+
+#[test]
+fn tune_weights_round_trip() {
+    let defaults = EvalParams::default();
+    let weights = defaults.to_weights();
+    assert_eq!(EvalParams::from_weights(&weights), defaults);
+    // a Score flattens to its mg then eg value
+    assert_eq!(
+        weights[2..4],
+        [defaults.piece_values[1].mg(), defaults.piece_values[1].eg()]
+    );
+
+    let mut changed = weights.clone();
+    for (i, weight) in changed.iter_mut().enumerate() {
+        *weight += i as i32 % 7 - 3;
+    }
+    let params = EvalParams::from_weights(&changed);
+    assert_eq!(params.to_weights(), changed);
+    assert_ne!(params, defaults);
+    assert_eq!(params.piece_values[0], defaults.piece_values[0] + s(-3, -2));
+}
@@ -0,0 +1,154 @@
+use copperfish::uci::{PositionCommand, SearchLimits, UciFormat, UciMoveError, move_from_uci};
+use crabchess::prelude::*;
+use std::time::Duration;
+
+fn limits(command: &str, turn: Color) -> SearchLimits {
+    SearchLimits::parse(command.split_whitespace(), turn)
+}
+
+#[test]
+fn go_limits_without_time_controls() {
+    let bare = limits("", Color::White);
+    assert_eq!(bare.time, Some(Duration::from_millis(2990)));
+
+    let depth = limits("depth 12", Color::White);
+    assert_eq!((depth.depth, depth.time), (Some(12), None));
+    let mate = limits("mate 3", Color::Black);
+    assert_eq!((mate.mate, mate.time), (Some(3), None));
+    let nodes = limits("nodes 50000", Color::White);
+    assert_eq!((nodes.nodes, nodes.time), (Some(50000), None));
+}
+
+#[test]
+fn go_limits_with_time_controls() {
+    let movetime = limits("depth 8 movetime 500", Color::White);
+    assert_eq!(
+        (movetime.depth, movetime.time),
+        (Some(8), Some(Duration::from_millis(500)))
+    );
+
+    // the clock of the side to move, shared over 30 moves plus half the increment
+    let clock = "wtime 60000 btime 30000 winc 1000 binc 2000";
+    assert_eq!(
+        limits(clock, Color::White).time,
+        Some(Duration::from_millis(2500))
+    );
+    assert_eq!(
+        limits(clock, Color::Black).time,
+        Some(Duration::from_millis(2000))
+    );
+
+    let moves_to_go = limits("wtime 10000 btime 10000 movestogo 4", Color::White);
+    assert_eq!(moves_to_go.time, Some(Duration::from_millis(2500)));
+    // never closer than 50 ms to the flag
+    let flag = limits("wtime 100 btime 100 winc 1000 movestogo 1", Color::White);
+    assert_eq!(flag.time, Some(Duration::from_millis(50)));
+}
+
+fn resolve(fen: &str, text: &str) -> Result<Move, UciMoveError> {
+    let position = ChessPosition::from_fen(fen).unwrap();
+    let turn = if fen.split(' ').nth(1) == Some("b") {
+        Color::Black
+    } else {
+        Color::White
+    };
+    move_from_uci(&position, turn, text)
+}
+
+fn position(command: &str) -> Result<PositionCommand, String> {
+    PositionCommand::parse(command.split_whitespace())
+}
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[test]
+fn uci_moves_malformed_and_illegal() {
+    for text in [
+        "", "e2e", "e2e4x", "e2e4qq", "z9e4", "e2e9", "e7e8k", "e2é4",
+    ] {
+        assert_eq!(
+            resolve(START, text),
+            Err(UciMoveError::Malformed(text.to_string())),
+            "{}",
+            text
+        );
+    }
+    for text in ["e2e5", "e7e5", "e1g1", "a2a3q", "g1g3"] {
+        assert_eq!(
+            resolve(START, text),
+            Err(UciMoveError::Illegal(text.to_string())),
+            "{}",
+            text
+        );
+    }
+    assert_eq!(
+        UciMoveError::Illegal("e2e5".to_string()).to_string(),
+        "Illegal move e2e5"
+    );
+    assert_eq!(resolve(START, "g1f3").unwrap().uci(), "g1f3");
+}
+
+#[test]
+fn uci_moves_castling_en_passant_and_promotion() {
+    // e1g1 castles only when the king on e1 has the right to
+    let castle = resolve("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1");
+    assert!(matches!(castle, Ok(Move::Castle { .. })), "{:?}", castle);
+    assert_eq!(
+        resolve("4k3/8/8/8/8/8/8/4K2R w - - 0 1", "e1g1"),
+        Err(UciMoveError::Illegal("e1g1".to_string()))
+    );
+    let rook = resolve("k7/8/8/8/8/8/7K/4R3 w - - 0 1", "e1g1");
+    assert!(matches!(rook, Ok(Move::Standard { .. })), "{:?}", rook);
+
+    // a pawn takes diagonally onto an empty square only en passant
+    let en_passant = resolve("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6");
+    assert!(
+        matches!(en_passant, Ok(Move::EnPassant { .. })),
+        "{:?}",
+        en_passant
+    );
+    assert_eq!(
+        resolve("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1", "e5d6"),
+        Err(UciMoveError::Illegal("e5d6".to_string()))
+    );
+
+    let promotion = resolve("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b7b8n");
+    assert!(
+        matches!(promotion, Ok(Move::PawnPromotion { .. })),
+        "{:?}",
+        promotion
+    );
+    assert_eq!(
+        resolve("8/1P2k3/8/8/8/8/8/4K3 w - - 0 1", "b7b8"),
+        Err(UciMoveError::Illegal("b7b8".to_string()))
+    );
+}
+
+#[test]
+fn position_commands() {
+    let start = position("startpos").unwrap();
+    assert_eq!(start.turn, Color::White);
+    assert!(start.moves.is_empty() && start.error.is_none());
+
+    // en passant on the square the last move skipped
+    let moves = position("startpos moves e2e4 a7a6 e4e5 d7d5 e5d6").unwrap();
+    let played: Vec<String> = moves.moves.iter().map(|mv| mv.uci()).collect();
+    assert_eq!(played, ["e2e4", "a7a6", "e4e5", "d7d5", "e5d6"]);
+    assert!(matches!(moves.moves[4], Move::EnPassant { .. }));
+    assert!(moves.error.is_none());
+
+    // the side to move comes from the FEN
+    let fen = position("fen 4k3/8/8/8/8/8/8/4K2R b K - 0 1 moves e8d7 e1g1").unwrap();
+    assert_eq!(fen.turn, Color::Black);
+    assert_eq!(fen.moves.len(), 2);
+    assert!(matches!(fen.moves[1], Move::Castle { .. }));
+
+    // the moves after a bad one are dropped
+    let bad = position("startpos moves e2e4 e2e4 d7d5").unwrap();
+    assert_eq!(bad.moves.len(), 1);
+    assert_eq!(bad.error, Some(UciMoveError::Illegal("e2e4".to_string())));
+
+    assert!(position("fen 8/8/8/8 w - - 0 1").is_err());
+    assert!(position("").is_err());
+    assert!(position("moves e2e4").is_err());
+}